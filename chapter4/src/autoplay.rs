@@ -0,0 +1,72 @@
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+use crate::card::{deal, exchange, shuffled_deck};
+use crate::hand::{evaluate, ROLES};
+use crate::strategy::Strategy;
+
+// 戦略ごとの成績
+struct Stats {
+    // 役ごとの出現回数 (ROLES と同じ順番)
+    role_counts: [u32; 5],
+    // 配当の合計
+    total_payout: u32,
+    // 入れ替えで役が強くなった回数
+    improved: u32,
+}
+
+// 同じシードから作った同じDeckを全ての戦略に配り、deals 回分の成績を比較する
+pub fn run(strategies: &[Box<dyn Strategy>], deals: usize, seed: u64) {
+    let mut stats: Vec<Stats> = strategies
+        .iter()
+        .map(|_| Stats {
+            role_counts: [0; 5],
+            total_payout: 0,
+            improved: 0,
+        })
+        .collect();
+
+    let mut seeds = StdRng::seed_from_u64(seed);
+    for _ in 0..deals {
+        let deal_seed = seeds.next_u64();
+        let deck = shuffled_deck(&mut StdRng::seed_from_u64(deal_seed));
+
+        for (strategy, stats) in strategies.iter().zip(stats.iter_mut()) {
+            let mut deck = deck.clone();
+            let mut rng = StdRng::seed_from_u64(deal_seed);
+
+            let mut hand = deal(&mut deck);
+            let before = evaluate(&hand);
+            let discards = strategy.choose_discards(&hand, &mut rng);
+            exchange(&mut hand, &mut deck, &discards);
+            let after = evaluate(&hand);
+
+            stats.role_counts[after as usize] += 1;
+            stats.total_payout += after.payout();
+            if after > before {
+                stats.improved += 1;
+            }
+        }
+    }
+
+    // 成績を表示
+    print!("{:<8}", "戦略");
+    for role in ROLES {
+        print!("\t{}", role.name());
+    }
+    println!("\t改善率\t平均配当");
+    for (strategy, stats) in strategies.iter().zip(&stats) {
+        print!("{:<8}", strategy.name());
+        for count in stats.role_counts {
+            print!("\t{:.1}%", percent(count, deals));
+        }
+        println!(
+            "\t{:.1}%\t{:.3}",
+            percent(stats.improved, deals),
+            stats.total_payout as f64 / deals as f64
+        );
+    }
+}
+
+fn percent(count: u32, deals: usize) -> f64 {
+    count as f64 * 100.0 / deals as f64
+}
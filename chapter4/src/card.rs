@@ -0,0 +1,106 @@
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Suit {
+    Club,
+    Diamond,
+    Heart,
+    Spade,
+}
+
+pub const SUITS: [Suit; 4] = [Suit::Club, Suit::Diamond, Suit::Heart, Suit::Spade];
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Card {
+    pub suit: Suit,
+    pub rank: i32,
+}
+
+// 52枚のDeckを作成
+pub fn new_deck() -> Vec<Card> {
+    let mut deck: Vec<Card> = Vec::new();
+    for suit in SUITS {
+        for rank in 1..=13 {
+            deck.push(Card { suit, rank });
+        }
+    }
+    deck
+}
+
+// シャッフル済みのDeckを作成
+pub fn shuffled_deck<R: Rng + ?Sized>(rng: &mut R) -> Vec<Card> {
+    let mut deck = new_deck();
+    deck.shuffle(rng);
+    deck
+}
+
+// Deckから5枚のカードを引いて、ランク順にソートする
+pub fn deal(deck: &mut Vec<Card>) -> Vec<Card> {
+    let mut hand: Vec<Card> = Vec::new();
+    for _ in 0..5 {
+        hand.push(deck.pop().unwrap());
+    }
+    sort_hand(&mut hand);
+    hand
+}
+
+// 指定した位置(0始まり)のカードをDeckから引いたカードと入れ替え、引いたカードを返す
+pub fn exchange(hand: &mut [Card], deck: &mut Vec<Card>, indices: &[usize]) -> Vec<Card> {
+    let mut drawn = Vec::new();
+    for &index in indices {
+        let card = deck.pop().unwrap();
+        hand[index] = card;
+        drawn.push(card);
+    }
+    sort_hand(hand);
+    drawn
+}
+
+pub fn sort_hand(hand: &mut [Card]) {
+    hand.sort_by_key(|c| c.rank);
+}
+
+// 入れ替えるカードの位置(0始まり)が手札の範囲内で、重複していないか確認する
+// メッセージでは、手札の表示と同じ1始まりの番号を使う
+pub fn validate_discards(indices: &[usize]) -> Result<(), String> {
+    for (i, &index) in indices.iter().enumerate() {
+        if index >= 5 {
+            return Err(format!(
+                "{}番のカードはありません。番号は1から5です",
+                index.saturating_add(1)
+            ));
+        }
+        if indices[..i].contains(&index) {
+            return Err(format!("{}番のカードが重複しています", index + 1));
+        }
+    }
+    Ok(())
+}
+
+// 入力された番号(1始まり、空白区切り)を、入れ替えるカードの位置(0始まり)にする
+pub fn parse_discards(line: &str) -> Result<Vec<usize>, String> {
+    let indices = line
+        .split_whitespace()
+        .map(|word| match word.parse::<usize>() {
+            Ok(number) if number >= 1 => Ok(number - 1),
+            _ => Err(format!("番号は1から5の数字で入力してください: {}", word)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    validate_discards(&indices)?;
+    Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_discards() {
+        assert_eq!(parse_discards("1 3 5\n"), Ok(vec![0, 2, 4]));
+        assert_eq!(parse_discards("\n"), Ok(vec![]));
+        for line in ["0", "6", "a", "-1", "2 2"] {
+            assert!(parse_discards(line).is_err(), "{}", line);
+        }
+    }
+}
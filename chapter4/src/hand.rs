@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+use crate::card::Card;
+
+// 役。強い役ほど後ろに並べているので、大小比較がそのまま役の強さになる
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum Role {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeCard,
+    Flush,
+}
+
+pub const ROLES: [Role; 5] = [
+    Role::HighCard,
+    Role::OnePair,
+    Role::TwoPair,
+    Role::ThreeCard,
+    Role::Flush,
+];
+
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::HighCard => "役なし",
+            Role::OnePair => "1ペア",
+            Role::TwoPair => "2ペア",
+            Role::ThreeCard => "スリーカード",
+            Role::Flush => "フラッシュ",
+        }
+    }
+
+    // 結果表示用のメッセージ
+    pub fn message(&self) -> String {
+        match self {
+            Role::HighCard => format!("{}...", self.name()),
+            _ => format!("{}！", self.name()),
+        }
+    }
+
+    // 役ごとの配当。戦略の期待値計算と成績比較に使う
+    pub fn payout(&self) -> u32 {
+        match self {
+            Role::HighCard => 0,
+            Role::OnePair => 1,
+            Role::TwoPair => 2,
+            Role::ThreeCard => 3,
+            Role::Flush => 5,
+        }
+    }
+}
+
+pub fn evaluate(hand: &[Card]) -> Role {
+    // フラッシュのチェック
+    let suit = hand.first().unwrap().suit;
+    let flash = hand.iter().all(|c| c.suit == suit);
+
+    // ペア数のチェック
+    let mut count = 0;
+    for i in 0..hand.len() - 1 {
+        for j in i + 1..hand.len() {
+            if hand[i].rank == hand[j].rank {
+                count += 1;
+            }
+        }
+    }
+
+    if flash {
+        Role::Flush
+    } else if count >= 3 {
+        Role::ThreeCard
+    } else if count == 2 {
+        Role::TwoPair
+    } else if count == 1 {
+        Role::OnePair
+    } else {
+        Role::HighCard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Suit;
+
+    fn hand(cards: &[(Suit, i32)]) -> Vec<Card> {
+        cards
+            .iter()
+            .map(|&(suit, rank)| Card { suit, rank })
+            .collect()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        use Suit::*;
+        let flush = hand(&[(Heart, 1), (Heart, 4), (Heart, 7), (Heart, 9), (Heart, 12)]);
+        assert_eq!(evaluate(&flush), Role::Flush);
+        let three = hand(&[(Club, 3), (Heart, 3), (Spade, 3), (Heart, 9), (Heart, 12)]);
+        assert_eq!(evaluate(&three), Role::ThreeCard);
+        let two = hand(&[(Club, 3), (Heart, 3), (Spade, 9), (Heart, 9), (Heart, 12)]);
+        assert_eq!(evaluate(&two), Role::TwoPair);
+        let one = hand(&[(Club, 3), (Heart, 3), (Spade, 5), (Heart, 9), (Heart, 12)]);
+        assert_eq!(evaluate(&one), Role::OnePair);
+        let none = hand(&[(Club, 1), (Heart, 3), (Spade, 5), (Heart, 9), (Heart, 12)]);
+        assert_eq!(evaluate(&none), Role::HighCard);
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::card::Card;
+use crate::hand::Role;
+
+pub const HISTORY_FILE: &str = "hand_history.jsonl";
+
+// 1回分の勝負の記録。履歴ファイルには1行に1件ずつJSONで書き込む
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HandRecord {
+    // シャッフルに使ったシード
+    pub seed: u64,
+    // 配られた手札
+    pub dealt: Vec<Card>,
+    // 入れ替えたカードの位置(0始まり)
+    pub discards: Vec<usize>,
+    // 入れ替えで引いたカード
+    pub replacements: Vec<Card>,
+    // 最終的な手札
+    pub hand: Vec<Card>,
+    // 最終的な役
+    pub role: Role,
+}
+
+// 履歴ファイルの末尾に記録を追加する
+pub fn append(path: &Path, record: &HandRecord) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let line = serde_json::to_string(record)?;
+    writeln!(file, "{}", line)
+}
+
+// 履歴ファイルから全ての記録を読み込む。ファイルがなければ記録は0件
+pub fn load(path: &Path) -> std::io::Result<Vec<HandRecord>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let reader = BufReader::new(file);
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(serde_json::from_str(&line)?);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Suit;
+
+    #[test]
+    fn test_append_and_load() {
+        let path = std::env::temp_dir().join(format!("simple-poker-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let card = |suit, rank| Card { suit, rank };
+        let record = HandRecord {
+            seed: 42,
+            dealt: vec![
+                card(Suit::Club, 1),
+                card(Suit::Heart, 3),
+                card(Suit::Spade, 3),
+                card(Suit::Heart, 9),
+                card(Suit::Diamond, 12),
+            ],
+            discards: vec![0],
+            replacements: vec![card(Suit::Club, 9)],
+            hand: vec![
+                card(Suit::Heart, 3),
+                card(Suit::Spade, 3),
+                card(Suit::Heart, 9),
+                card(Suit::Club, 9),
+                card(Suit::Diamond, 12),
+            ],
+            role: Role::TwoPair,
+        };
+        append(&path, &record).unwrap();
+        append(&path, &record).unwrap();
+
+        let records = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records, vec![record.clone(), record]);
+        assert!(load(&path).unwrap().is_empty());
+    }
+}
@@ -1,47 +1,182 @@
-use rand::seq::SliceRandom;
-
-#[derive(Copy, Clone, PartialEq, Debug)]
-enum Suit {
-    Club,
-    Diamond,
-    Heart,
-    Spade,
+mod autoplay;
+mod card;
+mod hand;
+mod history;
+mod net;
+mod strategy;
+
+use std::{
+    net::TcpListener,
+    num::{NonZeroU64, NonZeroUsize},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::{builder::RangedU64ValueParser, Args, Parser, Subcommand};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use card::{deal, exchange, parse_discards, validate_discards, Card};
+use hand::evaluate;
+use history::{HandRecord, HISTORY_FILE};
+
+#[derive(Parser)]
+#[clap()]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// 1人で遊ぶ (サブコマンド省略時と同じ)
+    Play(PlayArgs),
+    /// 戦略同士を対戦させて成績を比較する
+    Autoplay {
+        /// 配る回数 (1以上)
+        #[clap(long, default_value = "1000")]
+        deals: NonZeroUsize,
+        /// シャッフルに使うシード
+        #[clap(long, default_value_t = 0)]
+        seed: u64,
+        /// 比較する戦略 (stand, rule, ev)。省略時は全て
+        #[clap(long = "strategy")]
+        strategies: Vec<String>,
+    },
+    /// 履歴ファイルに記録された勝負を1ステップずつ再現する
+    Replay {
+        /// 再現する勝負の番号(1始まり)。省略時は最後の勝負
+        #[clap(long)]
+        hand: Option<usize>,
+        /// 履歴ファイル
+        #[clap(long)]
+        history: Option<PathBuf>,
+        /// ステップごとに Enter の入力を待たない
+        #[clap(long)]
+        no_wait: bool,
+    },
+    /// テーブルを立てて、TCP で接続してきたプレイヤー同士で対戦する
+    Server {
+        /// 待ち受けるアドレス
+        #[clap(long, default_value = "127.0.0.1:7878")]
+        addr: String,
+        /// 参加人数 (1人から5人まで)
+        #[clap(
+            long,
+            default_value_t = 2,
+            value_parser = RangedU64ValueParser::<usize>::new().range(1..=net::server::MAX_PLAYERS as u64)
+        )]
+        players: usize,
+        /// 応答を待つ秒数。過ぎたプレイヤーは降りたものとして扱う
+        #[clap(long, default_value = "60")]
+        timeout: NonZeroU64,
+        /// シャッフルに使うシード。省略時はランダム
+        #[clap(long)]
+        seed: Option<u64>,
+    },
+    /// サーバーのテーブルに参加する
+    Client {
+        /// 接続先のアドレス
+        #[clap(long, default_value = "127.0.0.1:7878")]
+        addr: String,
+        /// プレイヤー名
+        #[clap(long)]
+        name: String,
+    },
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
-struct Card {
-    suit: Suit,
-    rank: i32,
+#[derive(Args, Default)]
+struct PlayArgs {
+    /// シャッフルに使うシード。省略時はランダム
+    #[clap(long)]
+    seed: Option<u64>,
+    /// 履歴ファイル
+    #[clap(long)]
+    history: Option<PathBuf>,
 }
 
 fn main() {
-    let mut deck: Vec<Card> = Vec::new();
-    let suits = [Suit::Club, Suit::Diamond, Suit::Heart, Suit::Spade];
+    let cli = Cli::parse();
 
-    // Deckを作成
-    for suit in suits {
-        for rank in 1..=13 {
-            deck.push(Card { suit, rank });
+    match cli.command.unwrap_or(Commands::Play(PlayArgs::default())) {
+        Commands::Play(args) => play(&args),
+        Commands::Autoplay {
+            deals,
+            seed,
+            strategies,
+        } => {
+            let names = if strategies.is_empty() {
+                vec!["stand".to_string(), "rule".to_string(), "ev".to_string()]
+            } else {
+                strategies
+            };
+            let mut players = Vec::new();
+            for name in &names {
+                match strategy::from_name(name) {
+                    Some(player) => players.push(player),
+                    None => {
+                        println!("エラー：不明な戦略です: {}", name);
+                        return;
+                    }
+                }
+            }
+            autoplay::run(&players, deals.get(), seed);
+        }
+        Commands::Replay {
+            hand,
+            history,
+            no_wait,
+        } => {
+            let path = history.unwrap_or_else(|| PathBuf::from(HISTORY_FILE));
+            replay(&path, hand, !no_wait);
+        }
+        Commands::Server {
+            addr,
+            players,
+            seed,
+            timeout,
+        } => {
+            let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+            let listener = match TcpListener::bind(&addr) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    println!("エラー：{} で待ち受けられません: {}", addr, e);
+                    return;
+                }
+            };
+            println!("{} で{}人のプレイヤーを待っています", addr, players);
+            match net::server::run_table(
+                &listener,
+                players,
+                seed,
+                Duration::from_secs(timeout.get()),
+            ) {
+                Ok(results) => {
+                    for result in results {
+                        let mark = if result.winner { " (勝ち)" } else { "" };
+                        println!("{}: {}{}", result.name, result.role.message(), mark);
+                    }
+                }
+                Err(e) => println!("エラー：{}", e),
+            }
+        }
+        Commands::Client { addr, name } => {
+            if let Err(e) = net::client::run(&addr, &name, std::io::stdin().lock()) {
+                println!("エラー：{}", e);
+            }
         }
     }
+}
 
+fn play(args: &PlayArgs) {
     // Deckをシャッフル
-    let mut rng = rand::thread_rng();
-    deck.shuffle(&mut rng);
-
-    // Deckを表示
-    // for card in &deck {
-    //     println!("{:?} {:}", card.suit, card.rank);
-    // }
+    // シードを履歴に残しておけば、同じシードで同じ勝負を再現できる
+    let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut deck = card::shuffled_deck(&mut rng);
 
     // 5枚のカードを引く
-    let mut hand: Vec<Card> = Vec::new();
-    for _ in 0..5 {
-        hand.push(deck.pop().unwrap());
-    }
-
-    // 手札をソート
-    hand.sort_by(|a, b| a.rank.cmp(&b.rank));
+    let mut hand = deal(&mut deck);
+    let dealt = hand.clone();
 
     // 手札を表示
     println!("---Hand---");
@@ -54,49 +189,128 @@ fn main() {
     // 手札を交換
     // 標準入力から交換するカードの番号を入力
     // 例: 1 2 3
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input).unwrap();
-    let numbers: Vec<usize> = input
-        .split_whitespace()
-        .map(|x| x.parse().unwrap())
-        .collect::<Vec<usize>>();
-
-    for number in numbers {
-        hand[number - 1] = deck.pop().unwrap();
-    }
+    // 番号が正しくなければ入力し直してもらう
+    let numbers = loop {
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).unwrap();
+        match parse_discards(&input) {
+            Ok(numbers) => break numbers,
+            Err(message) => {
+                println!("エラー：{}", message);
+                println!("入れ替えたいカードの番号を入力してください(例: 1 2 3)");
+            }
+        }
+    };
 
-    // 手札をソート
-    hand.sort_by(|a, b| a.rank.cmp(&b.rank));
+    let replacements = exchange(&mut hand, &mut deck, &numbers);
 
     // 手札を表示
-    println!("---Hand---");
-    for card in &hand {
-        println!("{:?} {:}", card.suit, card.rank);
-    }
+    show_hand(&hand);
 
-    // フラッシュのチェック
-    let suit = hand.first().unwrap().suit;
-    let flash = hand.iter().all(|c| c.suit == suit);
+    let role = evaluate(&hand);
+    println!("{}", role.message());
 
-    // ペア数のチェック
-    let mut count = 0;
-    for i in 0..hand.len() - 1 {
-        for j in i + 1..hand.len() {
-            if hand[i].rank == hand[j].rank {
-                count += 1;
-            }
+    // 履歴ファイルに記録
+    let record = HandRecord {
+        seed,
+        dealt,
+        discards: numbers,
+        replacements,
+        hand,
+        role,
+    };
+    let path = args
+        .history
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(HISTORY_FILE));
+    match history::append(&path, &record) {
+        Ok(()) => println!("シード {} で記録しました", seed),
+        Err(e) => println!(
+            "エラー：履歴ファイル {} に記録できません: {}",
+            path.display(),
+            e
+        ),
+    }
+}
+
+fn replay(path: &Path, number: Option<usize>, wait: bool) {
+    let records = match history::load(path) {
+        Ok(records) => records,
+        Err(e) => {
+            println!(
+                "エラー：履歴ファイル {} を読めません: {}",
+                path.display(),
+                e
+            );
+            return;
         }
+    };
+    if records.is_empty() {
+        println!("エラー：{} に勝負が記録されていません", path.display());
+        return;
+    }
+    let number = number.unwrap_or(records.len());
+    let Some(record) = number.checked_sub(1).and_then(|i| records.get(i)) else {
+        println!("エラー：{}番目の勝負は記録されていません", number);
+        return;
+    };
+    // 手で書き換えた履歴ファイルでも、範囲外の位置で手札を参照しないように確かめる
+    let checked = if record.dealt.len() != 5 {
+        Err(format!("配られたカードが{}枚です", record.dealt.len()))
+    } else {
+        validate_discards(&record.discards)
+    };
+    if let Err(e) = checked {
+        println!(
+            "エラー：{} の{}番目の勝負が正しくありません: {}",
+            path.display(),
+            number,
+            e
+        );
+        return;
+    }
+
+    println!("=== {}番目の勝負 (シード {}) ===", number, record.seed);
+    println!("---Hand---");
+    for (i, card) in record.dealt.iter().enumerate() {
+        println!("{:}: {:?} {:}", i + 1, card.suit, card.rank);
     }
+    pause(wait);
 
-    if flash {
-        println!("フラッシュ！");
-    } else if count >= 3 {
-        println!("スリーカード！");
-    } else if count == 2 {
-        println!("2ペア！");
-    } else if count == 1 {
-        println!("1ペア！");
+    if record.discards.is_empty() {
+        println!("カードを入れ替えませんでした");
     } else {
-        println!("役なし...")
+        println!("入れ替えたカード:");
+        for (&index, card) in record.discards.iter().zip(&record.replacements) {
+            let old = record.dealt[index];
+            println!(
+                "{:}: {:?} {:} -> {:?} {:}",
+                index + 1,
+                old.suit,
+                old.rank,
+                card.suit,
+                card.rank
+            );
+        }
+    }
+    pause(wait);
+
+    show_hand(&record.hand);
+    println!("{}", record.role.message());
+}
+
+// Enter が押されるまで待つ
+fn pause(wait: bool) {
+    if wait {
+        println!("(Enter で次へ)");
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).unwrap();
+    }
+}
+
+fn show_hand(hand: &[Card]) {
+    println!("---Hand---");
+    for card in hand {
+        println!("{:?} {:}", card.suit, card.rank);
     }
 }
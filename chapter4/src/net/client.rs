@@ -0,0 +1,66 @@
+use std::{
+    io::{self, BufRead},
+    net::TcpStream,
+};
+
+use super::{ClientMessage, Connection, ServerMessage};
+use crate::card::parse_discards;
+
+// サーバーに接続して1回勝負する。入れ替えるカードの番号は input から読む
+pub fn run<R: BufRead>(addr: &str, name: &str, mut input: R) -> io::Result<()> {
+    let mut connection = Connection::new(TcpStream::connect(addr)?)?;
+    connection.send(&ClientMessage::Join {
+        name: name.to_string(),
+    })?;
+
+    loop {
+        match connection.receive()? {
+            ServerMessage::Welcome { seat, players } => {
+                println!(
+                    "{}番目の席に着きました。{}人揃うのを待っています...",
+                    seat + 1,
+                    players
+                );
+            }
+            ServerMessage::Hand { cards } => {
+                println!("---Hand---");
+                for (i, card) in cards.iter().enumerate() {
+                    println!("{:}: {:?} {:}", i + 1, card.suit, card.rank);
+                }
+                ask_discards(&mut connection, &mut input)?;
+            }
+            ServerMessage::Drawn { hand, .. } => {
+                println!("---Hand---");
+                for card in &hand {
+                    println!("{:?} {:}", card.suit, card.rank);
+                }
+                println!("他のプレイヤーを待っています...");
+            }
+            ServerMessage::Showdown { results } => {
+                println!("---Result---");
+                for result in results {
+                    let mark = if result.winner { " (勝ち)" } else { "" };
+                    println!("{}: {}{}", result.name, result.role.message(), mark);
+                }
+                return Ok(());
+            }
+            ServerMessage::Error { message } => {
+                println!("エラー：{}", message);
+                ask_discards(&mut connection, &mut input)?;
+            }
+        }
+    }
+}
+
+// 入れ替えるカードの番号(1始まり)を読んで、サーバーに送る
+fn ask_discards<R: BufRead>(connection: &mut Connection, input: &mut R) -> io::Result<()> {
+    loop {
+        println!("入れ替えたいカードの番号を入力してください(例: 1 2 3)");
+        let mut line = String::new();
+        input.read_line(&mut line)?;
+        match parse_discards(&line) {
+            Ok(indices) => return connection.send(&ClientMessage::Discard { indices }),
+            Err(message) => println!("エラー：{}", message),
+        }
+    }
+}
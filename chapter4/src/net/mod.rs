@@ -0,0 +1,81 @@
+// TCP で対戦するための通信部分
+// メッセージは1行に1つのJSONで送受信する。プロトコルの詳細は README.md を参照
+pub mod client;
+pub mod server;
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::TcpStream,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::card::Card;
+use crate::hand::Role;
+
+// クライアントからサーバーへのメッセージ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    // テーブルに参加する
+    Join { name: String },
+    // 入れ替えるカードの位置(0始まり)を伝える
+    Discard { indices: Vec<usize> },
+}
+
+// サーバーからクライアントへのメッセージ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    // 参加を受け付けた。seat は0始まりの席番号
+    Welcome { seat: usize, players: usize },
+    // 配られた手札。本人にだけ送る
+    Hand { cards: Vec<Card> },
+    // 入れ替えで引いたカードと、入れ替え後の手札。本人にだけ送る
+    Drawn { cards: Vec<Card>, hand: Vec<Card> },
+    // 全員の役と勝者。他のプレイヤーの手札は含めない
+    Showdown { results: Vec<PlayerResult> },
+    // 不正なメッセージを受け取った
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerResult {
+    pub name: String,
+    pub role: Role,
+    pub winner: bool,
+}
+
+// JSON Lines でメッセージをやり取りする接続
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        let writer = stream.try_clone()?;
+        Ok(Self {
+            reader: BufReader::new(stream),
+            writer,
+        })
+    }
+
+    pub fn send<T: Serialize>(&mut self, message: &T) -> io::Result<()> {
+        let line = serde_json::to_string(message)?;
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()
+    }
+
+    // JSON として読めない行は InvalidData のエラーにする (切断とは区別する)
+    pub fn receive<T: DeserializeOwned>(&mut self) -> io::Result<T> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "接続が切断されました",
+            ));
+        }
+        serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
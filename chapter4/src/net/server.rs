@@ -0,0 +1,316 @@
+use std::{io, net::TcpListener, time::Duration};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::{ClientMessage, Connection, PlayerResult, ServerMessage};
+use crate::card::{deal, exchange, shuffled_deck, validate_discards, Card};
+use crate::hand::evaluate;
+
+// 5人までなら、全員が5枚入れ替えても52枚のDeckが足りる
+pub const MAX_PLAYERS: usize = 5;
+
+struct Player {
+    name: String,
+    connection: Connection,
+    hand: Vec<Card>,
+}
+
+// players 人が揃うまで待ってから1回勝負し、結果を返す
+// Deckと手札はサーバーだけが持ち、各クライアントには本人の手札しか送らない
+// 途中で接続が切れたプレイヤーや、timeout を過ぎても応答しないプレイヤーは
+// 降りたものとして扱い、残りのプレイヤーで勝負を続ける
+pub fn run_table(
+    listener: &TcpListener,
+    players: usize,
+    seed: u64,
+    timeout: Duration,
+) -> io::Result<Vec<PlayerResult>> {
+    if !(1..=MAX_PLAYERS).contains(&players) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("参加人数は1人から{}人までです", MAX_PLAYERS),
+        ));
+    }
+    // 参加者を待つ
+    let mut table: Vec<Player> = Vec::new();
+    while table.len() < players {
+        let (stream, addr) = listener.accept()?;
+        let joined = stream
+            .set_read_timeout(Some(timeout))
+            .and_then(|()| Connection::new(stream))
+            .and_then(|mut connection| {
+                let name = receive_valid(&mut connection, |message| match message {
+                    ClientMessage::Join { name } => Ok(name),
+                    _ => Err("最初に join を送ってください".to_string()),
+                })?;
+                connection.send(&ServerMessage::Welcome {
+                    seat: table.len(),
+                    players,
+                })?;
+                Ok(Player {
+                    name,
+                    connection,
+                    hand: Vec::new(),
+                })
+            });
+        match joined {
+            Ok(player) => {
+                println!("{} が参加しました ({})", player.name, addr);
+                table.push(player);
+            }
+            Err(e) => println!("{} からの参加を受け付けられませんでした: {}", addr, e),
+        }
+    }
+
+    // 手札を配る
+    let mut deck = shuffled_deck(&mut StdRng::seed_from_u64(seed));
+    table.retain_mut(|player| {
+        player.hand = deal(&mut deck);
+        let cards = player.hand.clone();
+        let sent = player.connection.send(&ServerMessage::Hand { cards });
+        still_connected(player, sent)
+    });
+
+    // 席順に入れ替えを受け付ける
+    table.retain_mut(|player| {
+        let exchanged = receive_valid(&mut player.connection, |message| match message {
+            ClientMessage::Discard { indices } => validate_discards(&indices).map(|()| indices),
+            _ => Err("discard を送ってください".to_string()),
+        })
+        .and_then(|indices| {
+            let cards = exchange(&mut player.hand, &mut deck, &indices);
+            let hand = player.hand.clone();
+            player
+                .connection
+                .send(&ServerMessage::Drawn { cards, hand })
+        });
+        still_connected(player, exchanged)
+    });
+
+    // 勝敗を決めて全員に知らせる
+    let roles: Vec<_> = table.iter().map(|p| evaluate(&p.hand)).collect();
+    let best = roles.iter().max().copied();
+    let results: Vec<PlayerResult> = table
+        .iter()
+        .zip(&roles)
+        .map(|(player, &role)| PlayerResult {
+            name: player.name.clone(),
+            role,
+            winner: Some(role) == best,
+        })
+        .collect();
+    for player in &mut table {
+        let sent = player.connection.send(&ServerMessage::Showdown {
+            results: results.clone(),
+        });
+        still_connected(player, sent);
+    }
+    Ok(results)
+}
+
+// parse が受け付けるメッセージが届くまで読む
+// 読めないメッセージや受け付けないメッセージには Error を返して、送り直してもらう
+// 接続が切れたときだけ Err を返す
+fn receive_valid<T>(
+    connection: &mut Connection,
+    parse: impl Fn(ClientMessage) -> Result<T, String>,
+) -> io::Result<T> {
+    loop {
+        let parsed = match connection.receive() {
+            Ok(message) => parse(message),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                Err(format!("メッセージを読めません: {}", e))
+            }
+            Err(e) => return Err(e),
+        };
+        match parsed {
+            Ok(value) => return Ok(value),
+            Err(message) => connection.send(&ServerMessage::Error { message })?,
+        }
+    }
+}
+
+// 送受信に失敗していたら (読み込みのタイムアウトも含む)、そのプレイヤーの接続は切れたものとして false を返す
+fn still_connected(player: &Player, result: io::Result<()>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            println!("{} の接続が切れました: {}", player.name, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpStream, thread};
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn join(addr: std::net::SocketAddr, name: &str) -> Connection {
+        let mut connection = Connection::new(TcpStream::connect(addr).unwrap()).unwrap();
+        connection
+            .send(&ClientMessage::Join {
+                name: name.to_string(),
+            })
+            .unwrap();
+        connection
+    }
+
+    #[test]
+    fn test_run_table_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || run_table(&listener, 2, 1, TIMEOUT).unwrap());
+
+        let mut alice = join(addr, "alice");
+        assert_eq!(
+            alice.receive::<ServerMessage>().unwrap(),
+            ServerMessage::Welcome {
+                seat: 0,
+                players: 2
+            }
+        );
+        let mut bob = join(addr, "bob");
+        assert_eq!(
+            bob.receive::<ServerMessage>().unwrap(),
+            ServerMessage::Welcome {
+                seat: 1,
+                players: 2
+            }
+        );
+
+        let ServerMessage::Hand { cards: alice_hand } = alice.receive().unwrap() else {
+            panic!("hand expected");
+        };
+        let ServerMessage::Hand { cards: bob_hand } = bob.receive().unwrap() else {
+            panic!("hand expected");
+        };
+        assert!(alice_hand.iter().all(|c| !bob_hand.contains(c)));
+
+        // 不正な位置はエラーになり、送り直せる
+        alice
+            .send(&ClientMessage::Discard { indices: vec![7] })
+            .unwrap();
+        assert!(matches!(
+            alice.receive().unwrap(),
+            ServerMessage::Error { .. }
+        ));
+        alice
+            .send(&ClientMessage::Discard {
+                indices: vec![0, 1],
+            })
+            .unwrap();
+        let ServerMessage::Drawn { cards, hand } = alice.receive().unwrap() else {
+            panic!("drawn expected");
+        };
+        assert_eq!(cards.len(), 2);
+        assert!(cards.iter().all(|c| hand.contains(c)));
+
+        bob.send(&ClientMessage::Discard { indices: vec![] })
+            .unwrap();
+        let ServerMessage::Drawn { cards, hand } = bob.receive().unwrap() else {
+            panic!("drawn expected");
+        };
+        assert!(cards.is_empty());
+        assert_eq!(hand, bob_hand);
+
+        let results = server.join().unwrap();
+        for connection in [&mut alice, &mut bob] {
+            assert_eq!(
+                connection.receive::<ServerMessage>().unwrap(),
+                ServerMessage::Showdown {
+                    results: results.clone()
+                }
+            );
+        }
+        assert_eq!(results[0].name, "alice");
+        assert_eq!(results[1].role, evaluate(&bob_hand));
+        assert!(results.iter().any(|r| r.winner));
+    }
+
+    #[test]
+    fn test_bad_messages_and_disconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || run_table(&listener, 2, 1, TIMEOUT).unwrap());
+
+        // 読めないメッセージにはエラーを返し、参加し直せる
+        let mut alice = Connection::new(TcpStream::connect(addr).unwrap()).unwrap();
+        alice.send(&"hello").unwrap();
+        assert!(matches!(
+            alice.receive().unwrap(),
+            ServerMessage::Error { .. }
+        ));
+        alice
+            .send(&ClientMessage::Join {
+                name: "alice".to_string(),
+            })
+            .unwrap();
+        assert!(matches!(
+            alice.receive().unwrap(),
+            ServerMessage::Welcome { seat: 0, .. }
+        ));
+
+        let mut bob = join(addr, "bob");
+        assert!(matches!(
+            bob.receive().unwrap(),
+            ServerMessage::Welcome { seat: 1, .. }
+        ));
+        assert!(matches!(
+            alice.receive().unwrap(),
+            ServerMessage::Hand { .. }
+        ));
+        assert!(matches!(bob.receive().unwrap(), ServerMessage::Hand { .. }));
+
+        alice
+            .send(&ClientMessage::Discard { indices: vec![] })
+            .unwrap();
+        assert!(matches!(
+            alice.receive().unwrap(),
+            ServerMessage::Drawn { .. }
+        ));
+        // 入れ替えの前に bob の接続が切れても、alice だけで勝負を終える
+        drop(bob);
+
+        let results = server.join().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "alice");
+        assert!(results[0].winner);
+        assert!(matches!(
+            alice.receive().unwrap(),
+            ServerMessage::Showdown { .. }
+        ));
+    }
+
+    #[test]
+    fn test_players_and_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(run_table(&listener, 0, 1, TIMEOUT).is_err());
+        assert!(run_table(&listener, MAX_PLAYERS + 1, 1, TIMEOUT).is_err());
+
+        let addr = listener.local_addr().unwrap();
+        let server =
+            thread::spawn(move || run_table(&listener, 2, 1, Duration::from_millis(200)).unwrap());
+        let mut alice = join(addr, "alice");
+        let mut bob = join(addr, "bob");
+        for connection in [&mut alice, &mut bob] {
+            assert!(matches!(
+                connection.receive().unwrap(),
+                ServerMessage::Welcome { .. }
+            ));
+            assert!(matches!(
+                connection.receive().unwrap(),
+                ServerMessage::Hand { .. }
+            ));
+        }
+        // alice が何も送らないまま時間が過ぎても、bob だけで勝負を終える
+        bob.send(&ClientMessage::Discard { indices: vec![] })
+            .unwrap();
+        let results = server.join().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "bob");
+    }
+}
@@ -0,0 +1,142 @@
+use rand::{seq::SliceRandom, RngCore};
+
+use crate::card::{new_deck, Card, SUITS};
+use crate::hand::evaluate;
+
+// 手札を見て、入れ替えるカードの位置(0始まり)を決める戦略
+pub trait Strategy {
+    fn name(&self) -> &str;
+    fn choose_discards(&self, hand: &[Card], rng: &mut dyn RngCore) -> Vec<usize>;
+}
+
+// 何も入れ替えない戦略
+pub struct StandPat;
+
+impl Strategy for StandPat {
+    fn name(&self) -> &str {
+        "stand"
+    }
+
+    fn choose_discards(&self, _hand: &[Card], _rng: &mut dyn RngCore) -> Vec<usize> {
+        Vec::new()
+    }
+}
+
+// 簡単なルールで入れ替える戦略
+// - 同じスートが4枚あれば、残りの1枚を入れ替えてフラッシュを狙う
+// - ペアになっているカードは残し、それ以外を入れ替える
+pub struct RuleBased;
+
+impl Strategy for RuleBased {
+    fn name(&self) -> &str {
+        "rule"
+    }
+
+    fn choose_discards(&self, hand: &[Card], _rng: &mut dyn RngCore) -> Vec<usize> {
+        for suit in SUITS {
+            if hand.iter().filter(|c| c.suit == suit).count() == 4 {
+                return (0..hand.len()).filter(|&i| hand[i].suit != suit).collect();
+            }
+        }
+        (0..hand.len())
+            .filter(|&i| !hand.iter().any(|c| c != &hand[i] && c.rank == hand[i].rank))
+            .collect()
+    }
+}
+
+// 入れ替え方32通りそれぞれについて、見えていないカードから引き直すシミュレーションを
+// samples 回ずつ行い、配当の期待値が最大になる入れ替え方を選ぶ戦略
+pub struct ExpectedValue {
+    pub samples: usize,
+}
+
+impl ExpectedValue {
+    fn expected_payout(
+        &self,
+        hand: &[Card],
+        unseen: &[Card],
+        discards: &[usize],
+        rng: &mut dyn RngCore,
+    ) -> f64 {
+        if discards.is_empty() {
+            return evaluate(hand).payout() as f64;
+        }
+        let mut total = 0;
+        let mut trial = hand.to_vec();
+        for _ in 0..self.samples {
+            let drawn = unseen.choose_multiple(rng, discards.len());
+            for (&index, &card) in discards.iter().zip(drawn) {
+                trial[index] = card;
+            }
+            total += evaluate(&trial).payout();
+        }
+        total as f64 / self.samples as f64
+    }
+}
+
+impl Strategy for ExpectedValue {
+    fn name(&self) -> &str {
+        "ev"
+    }
+
+    fn choose_discards(&self, hand: &[Card], rng: &mut dyn RngCore) -> Vec<usize> {
+        let unseen: Vec<Card> = new_deck()
+            .into_iter()
+            .filter(|c| !hand.contains(c))
+            .collect();
+
+        let mut best = Vec::new();
+        let mut best_payout = f64::MIN;
+        for mask in 0..(1u32 << hand.len()) {
+            let discards: Vec<usize> = (0..hand.len()).filter(|i| mask & (1 << i) != 0).collect();
+            let payout = self.expected_payout(hand, &unseen, &discards, rng);
+            if payout > best_payout {
+                best_payout = payout;
+                best = discards;
+            }
+        }
+        best
+    }
+}
+
+// 名前から戦略を作る
+pub fn from_name(name: &str) -> Option<Box<dyn Strategy>> {
+    match name {
+        "stand" => Some(Box::new(StandPat)),
+        "rule" => Some(Box::new(RuleBased)),
+        "ev" => Some(Box::new(ExpectedValue { samples: 200 })),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Suit::{self, *};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn hand(cards: &[(Suit, i32)]) -> Vec<Card> {
+        cards
+            .iter()
+            .map(|&(suit, rank)| Card { suit, rank })
+            .collect()
+    }
+
+    #[test]
+    fn test_rule_based() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let pair = hand(&[(Club, 2), (Heart, 2), (Spade, 5), (Heart, 9), (Diamond, 12)]);
+        assert_eq!(RuleBased.choose_discards(&pair, &mut rng), vec![2, 3, 4]);
+
+        let flush_draw = hand(&[(Heart, 2), (Heart, 4), (Spade, 4), (Heart, 9), (Heart, 12)]);
+        assert_eq!(RuleBased.choose_discards(&flush_draw, &mut rng), vec![2]);
+    }
+
+    #[test]
+    fn test_expected_value_keeps_made_flush() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let flush = hand(&[(Heart, 2), (Heart, 4), (Heart, 6), (Heart, 9), (Heart, 12)]);
+        let strategy = ExpectedValue { samples: 50 };
+        assert!(strategy.choose_discards(&flush, &mut rng).is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::currency::{self, Rates};
+use crate::error::Result;
+use crate::record::Record;
+use crate::report::format_table;
+
+// ある日付の時点での口座の残高 (基準通貨に換算した額)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Balance {
+    pub account: String,
+    pub amount: Decimal,
+}
+
+// 口座ごとに date までの記録を通貨ごとに合計し、date のレートで base に換算する
+pub fn compute(
+    accounts: &[(String, Vec<Record>)],
+    date: NaiveDate,
+    rates: &Rates,
+    base: &str,
+) -> Result<Vec<Balance>> {
+    let mut balances = Vec::new();
+    for (account, records) in accounts {
+        let mut totals: BTreeMap<&str, Decimal> = BTreeMap::new();
+        for record in records.iter().filter(|r| r.日付 <= date) {
+            *totals.entry(&record.通貨).or_default() += record.金額;
+        }
+        let mut amount = Decimal::ZERO;
+        for (currency, total) in totals {
+            amount += total * rates.rate(currency, base, date)?;
+        }
+        balances.push(Balance {
+            account: account.clone(),
+            amount: currency::round(amount, base),
+        });
+    }
+    Ok(balances)
+}
+
+// 全ての口座の残高の合計
+pub fn net_worth(balances: &[Balance]) -> Decimal {
+    balances.iter().map(|b| b.amount).sum()
+}
+
+pub fn render(balances: &[Balance], date: NaiveDate, base: &str) -> String {
+    let rows: Vec<Vec<String>> = balances
+        .iter()
+        .map(|b| vec![b.account.clone(), b.amount.to_string()])
+        .collect();
+    let footer = ["純資産".to_string(), net_worth(balances).to_string()];
+    format!(
+        "{} 時点 ({})\n{}",
+        date,
+        base,
+        format_table(&["口座", "残高"], &rows, Some(&footer))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(date: &str, amount: i32) -> Record {
+        Record::new(date.parse().unwrap(), "", amount)
+    }
+
+    fn dollars(date: &str, amount: i32) -> Record {
+        let mut record = record(date, amount);
+        record.通貨 = "USD".to_string();
+        record
+    }
+
+    #[test]
+    fn test_compute_as_of_date() {
+        let accounts = vec![
+            (
+                "財布".to_string(),
+                vec![record("2024-01-01", 10000), record("2024-02-01", -3000)],
+            ),
+            (
+                "カード".to_string(),
+                vec![record("2024-01-10", -5000), record("2024-01-31", -2000)],
+            ),
+            (
+                "証券".to_string(),
+                vec![dollars("2024-01-05", 100), dollars("2024-01-20", 50)],
+            ),
+        ];
+        let mut rates = Rates::default();
+        rates.insert("2024-01-01".parse().unwrap(), "USD", "JPY", 140.into());
+        rates.insert(
+            "2024-01-25".parse().unwrap(),
+            "USD",
+            "JPY",
+            "147.5".parse().unwrap(),
+        );
+        let balances = compute(&accounts, "2024-01-31".parse().unwrap(), &rates, "JPY").unwrap();
+        assert_eq!(
+            balances,
+            [
+                Balance {
+                    account: "財布".to_string(),
+                    amount: 10000.into(),
+                },
+                Balance {
+                    account: "カード".to_string(),
+                    amount: (-7000).into(),
+                },
+                // 記録の日付ではなく、残高の日付のレートで換算する
+                Balance {
+                    account: "証券".to_string(),
+                    amount: 22125.into(),
+                },
+            ]
+        );
+        assert_eq!(net_worth(&balances), 25125.into());
+    }
+}
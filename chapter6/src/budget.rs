@@ -0,0 +1,198 @@
+use std::{collections::BTreeSet, path::Path};
+
+use csv::Reader;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::ledger::csv::check_header;
+use crate::record::Record;
+use crate::report::format_table;
+
+pub const BUDGET_FILE: &str = "budget.csv";
+const BUDGET_HEADER: [&str; 3] = ["月", "分類", "予算"];
+
+// 予算ファイルの1行。月が * なら毎月の予算になる
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Budget {
+    pub 月: String,
+    pub 分類: String,
+    pub 予算: Decimal,
+}
+
+impl Budget {
+    fn applies_to(&self, month: &str) -> bool {
+        self.月 == "*" || self.月 == month
+    }
+}
+
+pub fn load(path: &Path) -> Result<Vec<Budget>> {
+    let mut reader = Reader::from_path(path).map_err(|e| Error::from_csv(e, path))?;
+    let header = reader.headers().map_err(|e| Error::from_csv(e, path))?;
+    check_header(header, path, &BUDGET_HEADER, &BUDGET_HEADER)?;
+    let mut budgets = Vec::new();
+    for (i, result) in reader.deserialize().enumerate() {
+        let budget: Budget = result.map_err(|e| Error::from_csv(e, path))?;
+        if budget.月 != "*" && !is_month(&budget.月) {
+            return Err(Error::Parse {
+                path: path.to_path_buf(),
+                line: i as u64 + 2,
+                column: 1,
+                message: format!("月は YYYY-MM か * で指定してください: {}", budget.月),
+            });
+        }
+        budgets.push(budget);
+    }
+    Ok(budgets)
+}
+
+fn is_month(s: &str) -> bool {
+    chrono::NaiveDate::parse_from_str(&format!("{}-01", s), "%Y-%m-%d").is_ok()
+}
+
+// ある月のある分類の予算と支出
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub month: String,
+    pub category: String,
+    pub budget: Decimal,
+    pub spent: Decimal,
+}
+
+impl Line {
+    pub fn remaining(&self) -> Decimal {
+        self.budget - self.spent
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.spent > self.budget
+    }
+}
+
+// 月ごと・分類ごとに予算と支出を比べる
+// 記録は予算と同じ通貨に換算しておくこと
+// month を省略すると、支出か予算のある全ての月を対象にする
+pub fn compare(budgets: &[Budget], records: &[Record], month: Option<&str>) -> Vec<Line> {
+    let months: BTreeSet<String> = match month {
+        Some(month) => [month.to_string()].into(),
+        None => records
+            .iter()
+            .filter(|r| r.金額 < Decimal::ZERO)
+            .map(|r| r.日付.format("%Y-%m").to_string())
+            .chain(budgets.iter().filter(|b| b.月 != "*").map(|b| b.月.clone()))
+            .collect(),
+    };
+
+    let mut lines = Vec::new();
+    for month in &months {
+        let categories: BTreeSet<&str> = budgets
+            .iter()
+            .filter(|b| b.applies_to(month))
+            .map(|b| b.分類.as_str())
+            .collect();
+        for category in categories {
+            // 月を指定した予算を * の予算より優先する
+            let budget = budgets
+                .iter()
+                .filter(|b| b.分類 == category && b.applies_to(month))
+                .max_by_key(|b| b.月 != "*")
+                .map_or(Decimal::ZERO, |b| b.予算);
+            let spent = records
+                .iter()
+                .filter(|r| r.金額 < Decimal::ZERO && r.分類 == category)
+                .filter(|r| r.日付.format("%Y-%m").to_string() == *month)
+                .map(|r| -r.金額)
+                .sum();
+            lines.push(Line {
+                month: month.clone(),
+                category: category.to_string(),
+                budget,
+                spent,
+            });
+        }
+    }
+    lines
+}
+
+pub fn render(lines: &[Line]) -> String {
+    let rows: Vec<Vec<String>> = lines
+        .iter()
+        .map(|line| {
+            let usage = if line.budget > Decimal::ZERO {
+                let percent = line.spent * Decimal::ONE_HUNDRED / line.budget;
+                format!("{}%", percent.floor())
+            } else {
+                "-".to_string()
+            };
+            vec![
+                line.month.clone(),
+                line.category.clone(),
+                line.budget.to_string(),
+                line.spent.to_string(),
+                line.remaining().to_string(),
+                usage,
+            ]
+        })
+        .collect();
+    format_table(
+        &["月", "分類", "予算", "支出", "残り", "使用率"],
+        &rows,
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(month: &str, category: &str, amount: i64) -> Budget {
+        Budget {
+            月: month.to_string(),
+            分類: category.to_string(),
+            予算: amount.into(),
+        }
+    }
+
+    fn expense(date: &str, category: &str, amount: i32) -> Record {
+        let mut record = Record::new(date.parse().unwrap(), "", -amount);
+        record.分類 = category.to_string();
+        record
+    }
+
+    #[test]
+    fn test_compare() {
+        let budgets = [
+            budget("*", "食費", 30000),
+            budget("2024-02", "食費", 20000),
+            budget("*", "趣味", 10000),
+        ];
+        let records = [
+            expense("2024-01-05", "食費", 12000),
+            expense("2024-01-20", "食費", 8000),
+            expense("2024-02-03", "食費", 25000),
+            expense("2024-02-04", "交通費", 1000),
+        ];
+        let lines = compare(&budgets, &records, None);
+        let summary: Vec<(&str, &str, Decimal, Decimal, bool)> = lines
+            .iter()
+            .map(|l| {
+                (
+                    l.month.as_str(),
+                    l.category.as_str(),
+                    l.budget,
+                    l.spent,
+                    l.is_over(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("2024-01", "趣味", 10000.into(), 0.into(), false),
+                ("2024-01", "食費", 30000.into(), 20000.into(), false),
+                ("2024-02", "趣味", 10000.into(), 0.into(), false),
+                ("2024-02", "食費", 20000.into(), 25000.into(), true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::register_font;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+use crate::error::{Error, Result};
+use crate::record::Record;
+use crate::report::{GroupBy, Report};
+
+const WIDTH: u32 = 960;
+const HEIGHT: u32 = 600;
+const FONT: &str = "sans-serif";
+
+// --font を指定しなかったときに探すフォント。日本語を表示できるものを先に探す
+const FONT_CANDIDATES: [&str; 7] = [
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/opentype/ipaexfont-gothic/ipaexg.ttf",
+    "/usr/share/fonts/truetype/fonts-japanese-gothic.ttf",
+    "/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+    "C:\\Windows\\Fonts\\meiryo.ttc",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+];
+
+#[derive(Copy, Clone, PartialEq, Debug, ValueEnum)]
+pub enum Kind {
+    /// 期間ごとの収入と支出の棒グラフ
+    Bar,
+    /// 分類ごとの支出の円グラフ
+    Pie,
+    /// 期間ごとの残高の折れ線グラフ
+    Line,
+}
+
+// グラフに描くデータ。金額は描画のために f64 にしておく
+#[derive(Debug, Clone, PartialEq)]
+pub enum Data {
+    // (期間, 収入, 支出)
+    Bar(Vec<(String, f64, f64)>),
+    // (分類, 支出)。支出の多い順
+    Pie(Vec<(String, f64)>),
+    // (期間, 残高)
+    Line(Vec<(String, f64)>),
+}
+
+fn to_f64(amount: Decimal) -> f64 {
+    amount.to_f64().unwrap_or_default()
+}
+
+impl Data {
+    // 記録を集計する。円グラフは group_by によらず分類ごとに集計する
+    // 記録は全て同じ通貨に換算しておくこと
+    pub fn new(
+        kind: Kind,
+        records: &[Record],
+        group_by: GroupBy,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Self {
+        match kind {
+            Kind::Bar => {
+                let report = Report::new(records, group_by, from, to);
+                Data::Bar(
+                    report
+                        .rows
+                        .into_iter()
+                        .map(|r| (r.key, to_f64(r.income), to_f64(r.expense)))
+                        .collect(),
+                )
+            }
+            Kind::Pie => {
+                let report = Report::new(records, GroupBy::Category, from, to);
+                let mut slices: Vec<(String, f64)> = report
+                    .rows
+                    .into_iter()
+                    .filter(|r| r.expense > Decimal::ZERO)
+                    .map(|r| {
+                        let key = if r.key.is_empty() {
+                            "未分類".to_string()
+                        } else {
+                            r.key
+                        };
+                        (key, to_f64(r.expense))
+                    })
+                    .collect();
+                slices.sort_by(|a, b| b.1.total_cmp(&a.1));
+                Data::Pie(slices)
+            }
+            Kind::Line => {
+                let report = Report::new(records, group_by, from, to);
+                Data::Line(
+                    report
+                        .rows
+                        .into_iter()
+                        .map(|r| (r.key, to_f64(r.balance)))
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Data::Bar(_) => "収入と支出",
+            Data::Pie(_) => "分類ごとの支出",
+            Data::Line(_) => "残高の推移",
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Data::Bar(rows) => rows.is_empty(),
+            Data::Pie(slices) => slices.is_empty(),
+            Data::Line(points) => points.is_empty(),
+        }
+    }
+
+    // 拡張子が .png なら PNG に、それ以外は SVG に書き出す
+    pub fn render(&self, path: &Path, font: Option<&Path>) -> Result<()> {
+        if self.is_empty() {
+            return Err(Error::Chart("グラフに描く記録がありません".to_string()));
+        }
+        load_font(font)?;
+        let is_png = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("png"));
+        if is_png {
+            let root = BitMapBackend::new(path, (WIDTH, HEIGHT)).into_drawing_area();
+            self.draw(&root)
+                .and_then(|_| root.present())
+                .map_err(|e| Error::Chart(e.to_string()))
+        } else {
+            let root = SVGBackend::new(path, (WIDTH, HEIGHT)).into_drawing_area();
+            self.draw(&root)
+                .and_then(|_| root.present())
+                .map_err(|e| Error::Chart(e.to_string()))
+        }
+    }
+
+    fn draw<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+    ) -> std::result::Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+        root.fill(&WHITE)?;
+        let root = root.titled(self.title(), (FONT, 28))?;
+        match self {
+            Data::Bar(rows) => draw_bar(&root, rows),
+            Data::Pie(slices) => draw_pie(&root, slices),
+            Data::Line(points) => draw_line(&root, points),
+        }
+    }
+}
+
+// 期間の名前を、区切りの中央に表示する
+fn segment_label(labels: &[String], value: &SegmentValue<usize>) -> String {
+    match value {
+        SegmentValue::CenterOf(i) => labels.get(*i).cloned().unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+fn draw_bar<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    rows: &[(String, f64, f64)],
+) -> std::result::Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let labels: Vec<String> = rows.iter().map(|r| r.0.clone()).collect();
+    let max = rows.iter().map(|r| r.1.max(r.2)).fold(0.0, f64::max);
+    let max = if max > 0.0 { max * 1.1 } else { 1.0 };
+    let mut chart = ChartBuilder::on(root)
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(80)
+        .build_cartesian_2d((0..rows.len() - 1).into_segmented(), 0.0..max)?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(rows.len())
+        .x_label_formatter(&|v| segment_label(&labels, v))
+        .y_label_formatter(&|v| format!("{:.0}", v))
+        .label_style((FONT, 14))
+        .draw()?;
+
+    // 区切りの左半分に収入、右半分に支出を描く
+    let income = BLUE.mix(0.7);
+    let expense = RED.mix(0.7);
+    chart
+        .draw_series(rows.iter().enumerate().map(|(i, row)| {
+            let mut bar = Rectangle::new(
+                [
+                    (SegmentValue::Exact(i), 0.0),
+                    (SegmentValue::CenterOf(i), row.1),
+                ],
+                income.filled(),
+            );
+            bar.set_margin(0, 0, 4, 1);
+            bar
+        }))?
+        .label("収入")
+        .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], income.filled()));
+    chart
+        .draw_series(rows.iter().enumerate().map(|(i, row)| {
+            let mut bar = Rectangle::new(
+                [
+                    (SegmentValue::CenterOf(i), 0.0),
+                    (SegmentValue::Exact(i + 1), row.2),
+                ],
+                expense.filled(),
+            );
+            bar.set_margin(0, 0, 1, 4);
+            bar
+        }))?
+        .label("支出")
+        .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], expense.filled()));
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .label_font((FONT, 14))
+        .draw()
+}
+
+fn draw_pie<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    slices: &[(String, f64)],
+) -> std::result::Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let (width, height) = root.dim_in_pixel();
+    let center = (width as i32 / 2, height as i32 / 2);
+    let radius = f64::from(width.min(height)) * 0.35;
+    let sizes: Vec<f64> = slices.iter().map(|s| s.1).collect();
+    let colors: Vec<RGBColor> = (0..slices.len())
+        .map(|i| {
+            let (r, g, b) = Palette99::pick(i).rgb();
+            RGBColor(r, g, b)
+        })
+        .collect();
+    let labels: Vec<&str> = slices.iter().map(|s| s.0.as_str()).collect();
+    let mut pie = Pie::new(&center, &radius, &sizes, &colors, &labels);
+    // 12時の位置から時計回りに描く
+    pie.start_angle(-90.0);
+    pie.label_style((FONT, 16).into_font().color(&BLACK));
+    pie.percentages((FONT, 14).into_font().color(&BLACK));
+    root.draw(&pie)
+}
+
+fn draw_line<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    points: &[(String, f64)],
+) -> std::result::Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let labels: Vec<String> = points.iter().map(|p| p.0.clone()).collect();
+    // 0 の線が見えるように、縦軸には 0 を含める
+    let min = points.iter().map(|p| p.1).fold(0.0, f64::min);
+    let max = points.iter().map(|p| p.1).fold(0.0, f64::max);
+    let margin = ((max - min) * 0.1).max(1.0);
+    let mut chart = ChartBuilder::on(root)
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(80)
+        .build_cartesian_2d(
+            (0..points.len() - 1).into_segmented(),
+            (min - margin)..(max + margin),
+        )?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(points.len())
+        .x_label_formatter(&|v| segment_label(&labels, v))
+        .y_label_formatter(&|v| format!("{:.0}", v))
+        .label_style((FONT, 14))
+        .draw()?;
+    let series = || {
+        points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (SegmentValue::CenterOf(i), p.1))
+    };
+    chart.draw_series(LineSeries::new(series(), BLUE.stroke_width(2)))?;
+    chart.draw_series(series().map(|point| Circle::new(point, 4, BLUE.filled())))?;
+    Ok(())
+}
+
+// 文字の大きさを測るのにフォントが必要なので、SVG でもフォントを読み込む
+fn load_font(font: Option<&Path>) -> Result<()> {
+    let path = match font {
+        Some(path) => path.to_path_buf(),
+        None => FONT_CANDIDATES
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.exists())
+            .ok_or_else(|| {
+                Error::Chart("フォントが見つかりません。--font で指定してください".to_string())
+            })?,
+    };
+    let bytes = std::fs::read(&path)?;
+    // 登録したフォントはプログラムの終わりまで使うので、解放しなくてよい
+    register_font(FONT, FontStyle::Normal, Box::leak(bytes.into_boxed_slice()))
+        .map_err(|_| Error::Chart(format!("フォント {} を読み込めません", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(date: &str, amount: i64, category: &str) -> Record {
+        let mut record = Record::new(date.parse().unwrap(), "", amount);
+        record.分類 = category.to_string();
+        record
+    }
+
+    #[test]
+    fn test_data_aggregates_records() {
+        let records = vec![
+            record("2024-01-25", 200000, ""),
+            record("2024-01-05", -3000, "食費"),
+            record("2024-02-05", -5000, "食費"),
+            record("2024-02-10", -12000, "交通費"),
+            record("2024-02-11", -800, ""),
+        ];
+        assert_eq!(
+            Data::new(Kind::Bar, &records, GroupBy::Month, None, None),
+            Data::Bar(vec![
+                ("2024-01".to_string(), 200000.0, 3000.0),
+                ("2024-02".to_string(), 0.0, 17800.0),
+            ])
+        );
+        assert_eq!(
+            Data::new(Kind::Pie, &records, GroupBy::Month, None, None),
+            Data::Pie(vec![
+                ("交通費".to_string(), 12000.0),
+                ("食費".to_string(), 8000.0),
+                ("未分類".to_string(), 800.0),
+            ])
+        );
+        assert_eq!(
+            Data::new(Kind::Line, &records, GroupBy::Month, None, None),
+            Data::Line(vec![
+                ("2024-01".to_string(), 197000.0),
+                ("2024-02".to_string(), 179200.0),
+            ])
+        );
+    }
+}
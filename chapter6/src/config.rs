@@ -0,0 +1,76 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+// 設定ファイル。--config で指定しなければ $XDG_CONFIG_HOME/monestie/config.toml を読む
+//
+// 例:
+//
+//   data_dir = "~/Documents/家計簿"
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    // 口座ファイルや SQLite のデータベースを置くディレクトリ
+    pub data_dir: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("monestie").join("config.toml"))
+    }
+
+    // ファイルがなければ、何も設定していないものとして扱う
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let text = std::fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| Error::InvalidConfig {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })
+    }
+
+    // データディレクトリ。設定がなければ $XDG_DATA_HOME/monestie にする
+    // どのディレクトリから実行しても、同じ口座を使えるようにするため
+    pub fn data_dir(&self) -> PathBuf {
+        match &self.data_dir {
+            Some(dir) => expand_home(dir),
+            None => dirs::data_dir()
+                .map(|dir| dir.join("monestie"))
+                .unwrap_or_else(|| PathBuf::from(".")),
+        }
+    }
+}
+
+// 先頭の ~ をホームディレクトリにする
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+
+        std::fs::write(&path, "data_dir = \"/srv/家計簿\"\n").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.data_dir(), PathBuf::from("/srv/家計簿"));
+
+        std::fs::write(&path, "data-dir = \"/srv\"\n").unwrap();
+        assert!(matches!(
+            Config::load(&path),
+            Err(Error::InvalidConfig { .. })
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+use std::{collections::BTreeMap, path::Path};
+
+use chrono::NaiveDate;
+use csv::Reader;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::ledger::csv::check_header;
+use crate::record::Record;
+
+pub const RATES_FILE: &str = "rates.csv";
+const RATES_HEADER: [&str; 3] = ["日付", "通貨ペア", "レート"];
+
+// 為替レートファイルの1行
+// 通貨ペアが USD/JPY でレートが 150.25 なら、1 USD = 150.25 JPY
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Row {
+    日付: NaiveDate,
+    通貨ペア: String,
+    レート: Decimal,
+}
+
+// 通貨ペアごとの、日付順の為替レート
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rates {
+    rates: BTreeMap<(String, String), BTreeMap<NaiveDate, Decimal>>,
+}
+
+impl Rates {
+    // ファイルがなければ、為替レートが1つもないものとして扱う
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut rates = Rates::default();
+        if !path.exists() {
+            return Ok(rates);
+        }
+        let mut reader = Reader::from_path(path).map_err(|e| Error::from_csv(e, path))?;
+        let header = reader.headers().map_err(|e| Error::from_csv(e, path))?;
+        check_header(header, path, &RATES_HEADER, &RATES_HEADER)?;
+        for (i, result) in reader.deserialize().enumerate() {
+            let row: Row = result.map_err(|e| Error::from_csv(e, path))?;
+            let parse_error = |message: String| Error::Parse {
+                path: path.to_path_buf(),
+                line: i as u64 + 2,
+                column: 0,
+                message,
+            };
+            let Some((from, to)) = row.通貨ペア.split_once('/') else {
+                return Err(parse_error(format!(
+                    "通貨ペアは USD/JPY のように書いてください: {}",
+                    row.通貨ペア
+                )));
+            };
+            if row.レート <= Decimal::ZERO {
+                return Err(parse_error(format!(
+                    "レートが正ではありません: {}",
+                    row.レート
+                )));
+            }
+            rates.insert(row.日付, from.trim(), to.trim(), row.レート);
+        }
+        Ok(rates)
+    }
+
+    // 通貨コードは大文字にそろえる (usd/jpy も USD/JPY と同じ)
+    pub fn insert(&mut self, date: NaiveDate, from: &str, to: &str, rate: Decimal) {
+        self.rates
+            .entry((from.to_uppercase(), to.to_uppercase()))
+            .or_default()
+            .insert(date, rate);
+    }
+
+    // date の時点での、1 from あたりの to の額
+    // その日のレートがなければ、それより前で一番新しいレートを使う
+    // from/to のレートがなければ to/from のレートの逆数を使う
+    pub fn rate(&self, from: &str, to: &str, date: NaiveDate) -> Result<Decimal> {
+        let (from, to) = (from.to_uppercase(), to.to_uppercase());
+        let (from, to) = (from.as_str(), to.as_str());
+        if from == to {
+            return Ok(Decimal::ONE);
+        }
+        let latest = |from: &str, to: &str| {
+            self.rates
+                .get(&(from.to_string(), to.to_string()))
+                .and_then(|rates| rates.range(..=date).next_back())
+                .map(|(date, rate)| (*date, *rate))
+        };
+        match (latest(from, to), latest(to, from)) {
+            (Some((d1, rate)), Some((d2, _))) if d1 >= d2 => Ok(rate),
+            (_, Some((_, inverse))) => Ok(Decimal::ONE / inverse),
+            (Some((_, rate)), None) => Ok(rate),
+            (None, None) => Err(Error::MissingRate {
+                pair: format!("{}/{}", from, to),
+                date,
+            }),
+        }
+    }
+
+    // 記録の金額を、記録の日付のレートで base の通貨に換算する
+    pub fn convert(&self, record: &Record, base: &str) -> Result<Record> {
+        let rate = self.rate(&record.通貨, base, record.日付)?;
+        Ok(Record {
+            金額: round(record.金額 * rate, base),
+            通貨: base.to_string(),
+            ..record.clone()
+        })
+    }
+
+    pub fn convert_all(&self, records: &[Record], base: &str) -> Result<Vec<Record>> {
+        records.iter().map(|r| self.convert(r, base)).collect()
+    }
+}
+
+// 通貨の補助単位の桁数に丸める (円は1円未満を、それ以外は2桁より下を丸める)
+// 端数は銀行型丸め (偶数への丸め) にする
+pub fn round(amount: Decimal, currency: &str) -> Decimal {
+    let digits = match currency {
+        "JPY" | "KRW" => 0,
+        _ => 2,
+    };
+    amount.round_dp_with_strategy(digits, RoundingStrategy::MidpointNearestEven)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn decimal(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_rate_uses_latest_rate_on_or_before_date() {
+        let mut rates = Rates::default();
+        rates.insert(date("2024-01-01"), "USD", "JPY", decimal("141.00"));
+        rates.insert(date("2024-02-01"), "USD", "JPY", decimal("146.50"));
+        rates.insert(date("2024-02-15"), "JPY", "USD", decimal("0.0080"));
+        rates.insert(date("2024-01-01"), "eur", "jpy", decimal("155.00"));
+        assert_eq!(
+            rates.rate("EUR", "JPY", date("2024-01-31")).unwrap(),
+            decimal("155.00")
+        );
+
+        assert_eq!(
+            rates.rate("USD", "JPY", date("2024-01-31")).unwrap(),
+            decimal("141")
+        );
+        assert_eq!(
+            rates.rate("USD", "JPY", date("2024-02-01")).unwrap(),
+            decimal("146.5")
+        );
+        // 2024-02-15 以降は JPY/USD の方が新しいので、その逆数を使う
+        assert_eq!(
+            rates.rate("USD", "JPY", date("2024-03-01")).unwrap(),
+            decimal("125")
+        );
+        assert!(matches!(
+            rates.rate("USD", "JPY", date("2023-12-31")),
+            Err(Error::MissingRate { .. })
+        ));
+    }
+
+    #[test]
+    fn test_convert_is_exact() {
+        let mut rates = Rates::default();
+        rates.insert(date("2024-01-01"), "USD", "JPY", decimal("141.35"));
+        let mut record = Record::new(date("2024-01-10"), "本", decimal("-12.99"));
+        record.通貨 = "USD".to_string();
+        let converted = rates.convert(&record, "JPY").unwrap();
+        // -12.99 * 141.35 = -1836.1365
+        assert_eq!(converted.金額, decimal("-1836"));
+        assert_eq!(converted.通貨, "JPY");
+        assert_eq!(round(decimal("0.125"), "USD"), decimal("0.12"));
+    }
+}
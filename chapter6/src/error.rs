@@ -0,0 +1,235 @@
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+
+use thiserror::Error;
+
+// monestie のエラー
+// 種類ごとに終了コードを分けている (clap の引数エラーは clap が 2 で終了する)
+//   1: 入出力エラー
+//   2: 引数エラー (同じ口座への振替、複数の記録に当てはまる番号、閉じた口座への記録)
+//   3: 口座や記録が見つからない
+//   4: 口座が既に存在する
+//   5: CSV や設定ファイルの内容が不正
+//   6: データベースのエラー
+//   7: 為替レートがない
+//   8: 複式簿記として釣り合わない記録がある
+//   9: グラフを描けない
+//  10: 暗号化や復号ができない (パスフレーズの間違いなど)
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("口座 {0} が見つかりません")]
+    AccountNotFound(String),
+    #[error("口座 {0} は既に存在します")]
+    DuplicateAccount(String),
+    #[error("口座 {0} から同じ口座へは振り替えられません")]
+    SameAccount(String),
+    #[error("口座 {0} は閉じています")]
+    AccountClosed(String),
+    #[error("番号 {0} の記録が見つかりません")]
+    RecordNotFound(String),
+    #[error("番号 {0} に当てはまる記録が複数あります")]
+    AmbiguousId(String),
+    #[error("口座 {0} には番号のない記録があります。assign-ids で番号をつけてください")]
+    MissingIds(String),
+    #[error("{}: ヘッダーが不正です (必要な列: {expected}): {found}", path.display())]
+    InvalidHeader {
+        path: PathBuf,
+        expected: String,
+        found: String,
+    },
+    #[error("{}:{line}:{column}: 読み込めませんでした: {message}", path.display())]
+    Parse {
+        path: PathBuf,
+        line: u64,
+        column: u64,
+        message: String,
+    },
+    #[error("{}: インポート設定が不正です: {message}", path.display())]
+    InvalidProfile { path: PathBuf, message: String },
+    #[error("{}: 設定ファイルが不正です: {message}", path.display())]
+    InvalidConfig { path: PathBuf, message: String },
+    #[error("仕訳帳に書き出せません: {0}")]
+    Journal(String),
+    #[error("{date} 以前の {pair} の為替レートがありません")]
+    MissingRate { pair: String, date: NaiveDate },
+    #[error("複式簿記として釣り合っていません: {0}")]
+    Unbalanced(String),
+    #[error("グラフを描けません: {0}")]
+    Chart(String),
+    #[error("{}: 復号できません (パスフレーズが違うか、ファイルが壊れています)", .0.display())]
+    Decrypt(PathBuf),
+    #[error("暗号化のエラー: {0}")]
+    Crypto(String),
+    #[error("入出力エラー: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("CSV エラー: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("データベースエラー: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Lang {
+    Ja,
+    En,
+}
+
+impl Lang {
+    // LC_ALL, LC_MESSAGES, LANG の順に見て、ja で始まっていれば日本語にする
+    pub fn detect() -> Self {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        if locale.is_empty() || locale.starts_with("ja") {
+            Lang::Ja
+        } else {
+            Lang::En
+        }
+    }
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io(_) => 1,
+            Error::SameAccount(_) | Error::AmbiguousId(_) | Error::AccountClosed(_) => 2,
+            Error::AccountNotFound(_) | Error::RecordNotFound(_) | Error::MissingIds(_) => 3,
+            Error::DuplicateAccount(_) => 4,
+            Error::InvalidHeader { .. }
+            | Error::Parse { .. }
+            | Error::InvalidProfile { .. }
+            | Error::InvalidConfig { .. }
+            | Error::Journal(_)
+            | Error::Csv(_) => 5,
+            Error::Sqlite(_) => 6,
+            Error::MissingRate { .. } => 7,
+            Error::Unbalanced(_) => 8,
+            Error::Chart(_) => 9,
+            Error::Decrypt(_) | Error::Crypto(_) => 10,
+        }
+    }
+
+    pub fn message(&self, lang: Lang) -> String {
+        match lang {
+            Lang::Ja => format!("エラー：{}", self),
+            Lang::En => format!("error: {}", self.english()),
+        }
+    }
+
+    fn english(&self) -> String {
+        match self {
+            Error::AccountNotFound(account) => format!("account {} not found", account),
+            Error::DuplicateAccount(account) => format!("account {} already exists", account),
+            Error::AccountClosed(account) => format!("account {} is closed", account),
+            Error::RecordNotFound(id) => format!("record {} not found", id),
+            Error::AmbiguousId(id) => format!("id {} matches more than one record", id),
+            Error::MissingIds(accounts) => format!(
+                "account {} has records without ids; run assign-ids first",
+                accounts
+            ),
+            Error::SameAccount(account) => {
+                format!("cannot transfer from account {} to itself", account)
+            }
+            Error::InvalidHeader {
+                path,
+                expected,
+                found,
+            } => format!(
+                "{}: invalid header (required columns: {}): {}",
+                path.display(),
+                expected,
+                found
+            ),
+            Error::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => format!(
+                "{}:{}:{}: failed to parse: {}",
+                path.display(),
+                line,
+                column,
+                message
+            ),
+            Error::InvalidProfile { path, message } => {
+                format!("{}: invalid import profile: {}", path.display(), message)
+            }
+            Error::InvalidConfig { path, message } => {
+                format!("{}: invalid configuration: {}", path.display(), message)
+            }
+            Error::Journal(message) => format!("cannot export journal: {}", message),
+            Error::MissingRate { pair, date } => {
+                format!("no {} exchange rate on or before {}", pair, date)
+            }
+            Error::Unbalanced(message) => format!("not balanced: {}", message),
+            Error::Chart(message) => format!("cannot draw chart: {}", message),
+            Error::Decrypt(path) => format!(
+                "{}: cannot decrypt (wrong passphrase or corrupted file)",
+                path.display()
+            ),
+            Error::Crypto(message) => format!("encryption error: {}", message),
+            Error::Io(e) => format!("I/O error: {}", e),
+            Error::Csv(e) => format!("CSV error: {}", e),
+            Error::Sqlite(e) => format!("database error: {}", e),
+        }
+    }
+
+    // CSV の読み込みエラーを、行番号と列番号(どちらも1始まり)付きのエラーにする
+    pub fn from_csv(error: csv::Error, path: impl Into<PathBuf>) -> Self {
+        if error.is_io_error() {
+            let csv::ErrorKind::Io(e) = error.into_kind() else {
+                unreachable!()
+            };
+            return Error::Io(e);
+        }
+        let path = path.into();
+        match error.kind() {
+            csv::ErrorKind::Deserialize { pos, err } => Error::Parse {
+                path,
+                line: pos.as_ref().map_or(0, |p| p.line()),
+                column: err.field().map_or(0, |f| f + 1),
+                message: err.kind().to_string(),
+            },
+            csv::ErrorKind::UnequalLengths {
+                pos,
+                expected_len,
+                len,
+            } => Error::Parse {
+                path,
+                line: pos.as_ref().map_or(0, |p| p.line()),
+                column: len.min(expected_len) + 1,
+                message: format!("列の数が {} ではなく {} です", expected_len, len),
+            },
+            _ => Error::Csv(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, serde::Deserialize)]
+    #[allow(dead_code)]
+    struct Row {
+        name: String,
+        count: i32,
+    }
+
+    #[test]
+    fn test_from_csv_reports_line_and_column() {
+        let data = "name,count\na,1\nb,abc\n";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let error = reader.deserialize::<Row>().find_map(|r| r.err()).unwrap();
+        match Error::from_csv(error, "test.csv") {
+            Error::Parse { line, column, .. } => assert_eq!((line, column), (3, 2)),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+}
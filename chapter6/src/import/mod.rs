@@ -0,0 +1,136 @@
+// 銀行やカード会社の明細を取り込む
+// CSV (インポート設定で列や文字コードを指定できる)、OFX、QIF に対応している
+pub mod ofx;
+pub mod profile;
+pub mod qif;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+
+use crate::error::{Error, Result};
+use crate::ledger;
+use crate::record::Record;
+use profile::Profile;
+
+#[derive(Copy, Clone, PartialEq, Debug, ValueEnum)]
+pub enum Format {
+    /// 拡張子から判断する (.ofx/.qfx なら OFX、.qif なら QIF、それ以外は CSV)
+    Auto,
+    Csv,
+    Ofx,
+    Qif,
+}
+
+impl Format {
+    fn detect(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("ofx") | Some("qfx") => Format::Ofx,
+            Some("qif") => Format::Qif,
+            _ => Format::Csv,
+        }
+    }
+}
+
+// 明細ファイルを読み込む
+// インポート設定は CSV のときだけ使う。設定がなければ口座ファイルと同じ形式の CSV として読む
+pub fn read(path: &Path, format: Format, profile: Option<&Path>) -> Result<Vec<Record>> {
+    let format = match format {
+        Format::Auto => Format::detect(path),
+        format => format,
+    };
+    match (format, profile) {
+        (Format::Csv, Some(profile_path)) => Profile::load(profile_path)?.read(path, profile_path),
+        (Format::Csv, None) => ledger::csv::read_file(path),
+        (Format::Ofx, _) => ofx::parse(&decode(&std::fs::read(path)?), path),
+        (Format::Qif, _) => qif::parse(&decode(&std::fs::read(path)?), path),
+        (Format::Auto, _) => unreachable!(),
+    }
+}
+
+// OFX と QIF は文字コードの指定がないことが多いので、UTF-8 でなければ Shift_JIS とみなす
+fn decode(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.trim_start_matches('\u{feff}').to_string(),
+        Err(_) => encoding_rs::SHIFT_JIS.decode(bytes).0.into_owned(),
+    }
+}
+
+// 既に口座にある記録と、日付・金額・通貨・用途が同じ記録を取り除く
+// 同じ日に同じ買い物を2回した場合に備えて、既存の1件は取り込む記録の1件とだけ対応させる
+// 戻り値は取り込む記録と、重複として除いた件数
+pub fn remove_duplicates(existing: &[Record], incoming: Vec<Record>) -> (Vec<Record>, usize) {
+    let key = |record: &Record| {
+        (
+            record.日付,
+            record.金額,
+            record.通貨.clone(),
+            record.用途.clone(),
+        )
+    };
+    let mut counts: HashMap<(NaiveDate, Decimal, String, String), usize> = HashMap::new();
+    for record in existing {
+        *counts.entry(key(record)).or_default() += 1;
+    }
+    let mut skipped = 0;
+    let mut records = Vec::new();
+    for record in incoming {
+        match counts.get_mut(&key(&record)) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                skipped += 1;
+            }
+            _ => records.push(record),
+        }
+    }
+    (records, skipped)
+}
+
+// 金額の文字列を読んで、読めなければ行番号付きのエラーにする
+fn parse_amount(s: &str, path: &Path, line: u64) -> Result<Decimal> {
+    profile::parse_amount(s).map_err(|message| Error::Parse {
+        path: path.to_path_buf(),
+        line,
+        column: 0,
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(date: &str, usage: &str, amount: i32) -> Record {
+        Record::new(date.parse().unwrap(), usage, amount)
+    }
+
+    #[test]
+    fn test_remove_duplicates() {
+        let existing = [
+            record("2024-01-05", "コンビニ", -500),
+            record("2024-01-25", "給料", 200000),
+        ];
+        let incoming = vec![
+            record("2024-01-05", "コンビニ", -500),
+            record("2024-01-05", "コンビニ", -500),
+            record("2024-01-25", "給料", 200000),
+            record("2024-02-05", "コンビニ", -500),
+        ];
+        let (records, skipped) = remove_duplicates(&existing, incoming);
+        assert_eq!(skipped, 2);
+        assert_eq!(
+            records,
+            [
+                record("2024-01-05", "コンビニ", -500),
+                record("2024-02-05", "コンビニ", -500),
+            ]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use chrono::NaiveDate;
+
+use crate::error::{Error, Result};
+use crate::record::{Record, DEFAULT_CURRENCY};
+
+// OFX の明細を読む
+// OFX 1.x (SGML、値の終了タグがない) と 2.x (XML) のどちらも、
+// <STMTTRN> ～ </STMTTRN> の中の DTPOSTED, TRNAMT, NAME, MEMO と、明細の通貨 CURDEF だけを見る
+pub fn parse(text: &str, path: &Path) -> Result<Vec<Record>> {
+    let currency = value(text, "CURDEF").unwrap_or(DEFAULT_CURRENCY);
+    let mut records = Vec::new();
+    let mut rest = text;
+    let mut offset = 0;
+    while let Some(start) = find_tag(rest, "<STMTTRN>") {
+        let body_start = start + "<STMTTRN>".len();
+        let end = find_tag(&rest[body_start..], "</STMTTRN>").ok_or_else(|| Error::Parse {
+            path: path.to_path_buf(),
+            line: line_of(text, offset + start),
+            column: 0,
+            message: "</STMTTRN> がありません".to_string(),
+        })?;
+        let body = &rest[body_start..body_start + end];
+        let line = line_of(text, offset + start);
+        let mut record = transaction(body, path, line)?;
+        record.通貨 = currency.to_string();
+        records.push(record);
+
+        let consumed = body_start + end + "</STMTTRN>".len();
+        rest = &rest[consumed..];
+        offset += consumed;
+    }
+    Ok(records)
+}
+
+fn transaction(body: &str, path: &Path, line: u64) -> Result<Record> {
+    let parse_error = |message: String| Error::Parse {
+        path: path.to_path_buf(),
+        line,
+        column: 0,
+        message,
+    };
+    let date =
+        value(body, "DTPOSTED").ok_or_else(|| parse_error("DTPOSTED がありません".to_string()))?;
+    // 20240105 や 20240105120000[+9:JST] のように書かれているので、先頭の8文字だけ使う
+    let date = date
+        .get(..8)
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y%m%d").ok())
+        .ok_or_else(|| parse_error(format!("日付 {} を読めません", date)))?;
+    let amount =
+        value(body, "TRNAMT").ok_or_else(|| parse_error("TRNAMT がありません".to_string()))?;
+    let amount = super::parse_amount(amount, path, line)?;
+    let usage = value(body, "NAME")
+        .or_else(|| value(body, "MEMO"))
+        .unwrap_or_default();
+    Ok(Record::new(date, usage, amount))
+}
+
+// <TAG>値 の値を返す。値は次のタグか行末までとする
+fn value<'a>(body: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let start = find_tag(body, &open)? + open.len();
+    let value = &body[start..];
+    let end = value.find(['<', '\n', '\r']).unwrap_or(value.len());
+    Some(value[..end].trim())
+}
+
+// タグ名は大文字・小文字を区別しない
+fn find_tag(text: &str, tag: &str) -> Option<usize> {
+    text.to_ascii_uppercase().find(tag)
+}
+
+fn line_of(text: &str, offset: usize) -> u64 {
+    text[..offset].matches('\n').count() as u64 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sgml() {
+        let text = "OFXHEADER:100\nDATA:OFXSGML\n\n<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS>\n\
+                    <CURDEF>JPY\n\
+                    <BANKTRANLIST>\n\
+                    <STMTTRN>\n<TRNTYPE>DEBIT\n<DTPOSTED>20240105120000[+9:JST]\n\
+                    <TRNAMT>-1500.00\n<FITID>1\n<NAME>書店\n</STMTTRN>\n\
+                    <STMTTRN>\n<TRNTYPE>CREDIT\n<DTPOSTED>20240125\n\
+                    <TRNAMT>200000\n<FITID>2\n<MEMO>給料\n</STMTTRN>\n\
+                    </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>\n";
+        let records = parse(text, Path::new("bank.ofx")).unwrap();
+        assert_eq!(
+            records,
+            [
+                Record::new("2024-01-05".parse().unwrap(), "書店", -1500),
+                Record::new("2024-01-25".parse().unwrap(), "給料", 200000),
+            ]
+        );
+    }
+}
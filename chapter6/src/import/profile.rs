@@ -0,0 +1,338 @@
+use std::path::Path;
+
+use chrono::NaiveDate;
+use csv::{ReaderBuilder, StringRecord};
+use encoding_rs::Encoding;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::record::{Record, DEFAULT_CURRENCY};
+
+// 銀行の明細 CSV を読み込むための設定。TOML ファイルで書く
+//
+// 例 (Shift_JIS で、入金と出金が別の列になっている明細):
+//
+//   encoding = "shift_jis"
+//   date_column = "取引日"
+//   date_format = "%Y/%m/%d"
+//   usage_column = "摘要"
+//   deposit_column = "お預入れ"
+//   withdrawal_column = "お引出し"
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    // 文字コード (utf-8, shift_jis など)
+    #[serde(default = "default_encoding")]
+    pub encoding: String,
+    // 区切り文字
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    // 1行目がヘッダーか。false のときは列を番号で指定する
+    #[serde(default = "default_true")]
+    pub has_header: bool,
+    // ヘッダーより前に読み飛ばす行数
+    #[serde(default)]
+    pub skip_rows: usize,
+    pub date_column: Column,
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    pub usage_column: Column,
+    // 符号付きの金額の列
+    pub amount_column: Option<Column>,
+    // 明細が出金を正の数で書いているなら true にする
+    #[serde(default)]
+    pub negate: bool,
+    // 入金と出金が別の列になっている場合の列
+    pub deposit_column: Option<Column>,
+    pub withdrawal_column: Option<Column>,
+    pub category_column: Option<Column>,
+    // 明細の通貨 (JPY, USD など)
+    #[serde(default = "default_currency")]
+    pub currency: String,
+}
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
+
+fn default_encoding() -> String {
+    "utf-8".to_string()
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".to_string()
+}
+
+// 列の指定。ヘッダー名か、1始まりの列番号
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl Column {
+    fn resolve(&self, header: Option<&StringRecord>) -> std::result::Result<usize, String> {
+        match (self, header) {
+            (Column::Index(0), _) => Err("列番号は1から数えます".to_string()),
+            (Column::Index(i), _) => Ok(i - 1),
+            (Column::Name(name), Some(header)) => header
+                .iter()
+                .position(|h| h.trim() == name)
+                .ok_or_else(|| format!("列 {} がありません", name)),
+            (Column::Name(name), None) => Err(format!(
+                "ヘッダーがないので列 {} は番号で指定してください",
+                name
+            )),
+        }
+    }
+}
+
+impl Profile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let profile: Profile = toml::from_str(&text).map_err(|e| Error::InvalidProfile {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        if profile.amount_column.is_none()
+            && profile.deposit_column.is_none()
+            && profile.withdrawal_column.is_none()
+        {
+            return Err(Error::InvalidProfile {
+                path: path.to_path_buf(),
+                message: "amount_column か deposit_column/withdrawal_column を指定してください"
+                    .to_string(),
+            });
+        }
+        // csv クレートの区切り文字は1バイトなので、全角の ，などは使えない
+        if !profile.delimiter.is_ascii() {
+            return Err(Error::InvalidProfile {
+                path: path.to_path_buf(),
+                message: format!(
+                    "delimiter には ASCII の文字を指定してください: {}",
+                    profile.delimiter
+                ),
+            });
+        }
+        Ok(profile)
+    }
+
+    // 明細ファイルを読み込んで、記録に変換する
+    pub fn read(&self, path: &Path, profile_path: &Path) -> Result<Vec<Record>> {
+        let encoding =
+            Encoding::for_label(self.encoding.as_bytes()).ok_or_else(|| Error::InvalidProfile {
+                path: profile_path.to_path_buf(),
+                message: format!("不明な文字コードです: {}", self.encoding),
+            })?;
+        let bytes = std::fs::read(path)?;
+        let (text, _, _) = encoding.decode(&bytes);
+        self.parse(&text, path, profile_path)
+    }
+
+    fn parse(&self, text: &str, path: &Path, profile_path: &Path) -> Result<Vec<Record>> {
+        let body = text
+            .lines()
+            .skip(self.skip_rows)
+            .fold(String::new(), |mut body, line| {
+                body.push_str(line);
+                body.push('\n');
+                body
+            });
+        let mut reader = ReaderBuilder::new()
+            .has_headers(self.has_header)
+            .delimiter(self.delimiter as u8)
+            .flexible(true)
+            .from_reader(body.as_bytes());
+        let header = if self.has_header {
+            Some(
+                reader
+                    .headers()
+                    .map_err(|e| Error::from_csv(e, path))?
+                    .clone(),
+            )
+        } else {
+            None
+        };
+        let resolve = |column: &Column| {
+            column
+                .resolve(header.as_ref())
+                .map_err(|message| Error::InvalidProfile {
+                    path: profile_path.to_path_buf(),
+                    message,
+                })
+        };
+        let date_column = resolve(&self.date_column)?;
+        let usage_column = resolve(&self.usage_column)?;
+        let amount_column = self.amount_column.as_ref().map(resolve).transpose()?;
+        let deposit_column = self.deposit_column.as_ref().map(resolve).transpose()?;
+        let withdrawal_column = self.withdrawal_column.as_ref().map(resolve).transpose()?;
+        let category_column = self.category_column.as_ref().map(resolve).transpose()?;
+
+        let mut records = Vec::new();
+        for result in reader.records() {
+            let row = result.map_err(|e| Error::from_csv(e, path))?;
+            let line = row.position().map_or(0, |p| p.line()) + self.skip_rows as u64;
+            let parse_error = |column: usize, message: String| Error::Parse {
+                path: path.to_path_buf(),
+                line,
+                column: column as u64 + 1,
+                message,
+            };
+            let field = |column: usize| row.get(column).unwrap_or("").trim();
+
+            // 空行や合計行など、日付のない行は読み飛ばす
+            if field(date_column).is_empty() {
+                continue;
+            }
+            let date = NaiveDate::parse_from_str(field(date_column), &self.date_format)
+                .map_err(|e| parse_error(date_column, format!("日付 {}", e)))?;
+
+            let mut amount = Decimal::ZERO;
+            if let Some(column) = amount_column {
+                amount += parse_amount(field(column)).map_err(|m| parse_error(column, m))?;
+            }
+            if let Some(column) = deposit_column {
+                amount += parse_amount(field(column)).map_err(|m| parse_error(column, m))?;
+            }
+            if let Some(column) = withdrawal_column {
+                amount -= parse_amount(field(column)).map_err(|m| parse_error(column, m))?;
+            }
+            if self.negate {
+                amount = -amount;
+            }
+
+            let mut record = Record::new(date, field(usage_column), amount);
+            record.通貨 = self.currency.clone();
+            if let Some(column) = category_column {
+                record.分類 = field(column).to_string();
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+// "1,234" や "¥1,234"、"△1,234" (マイナス)、"(1,234)" (マイナス) のような金額を読む
+// 空欄は 0 として扱う
+pub fn parse_amount(s: &str) -> std::result::Result<Decimal, String> {
+    let mut s: String = s
+        .chars()
+        .filter(|c| !matches!(c, ',' | '，' | '¥' | '￥' | '円' | '$' | ' ' | '　'))
+        .collect();
+    if s.is_empty() {
+        return Ok(Decimal::ZERO);
+    }
+    let mut negative = false;
+    if let Some(rest) = s.strip_prefix(['-', '△', '▲']) {
+        negative = true;
+        s = rest.to_string();
+    } else if s.starts_with('(') && s.ends_with(')') {
+        negative = true;
+        s = s[1..s.len() - 1].to_string();
+    }
+    let value: Decimal = s.parse().map_err(|_| format!("金額 {} を読めません", s))?;
+    Ok(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_amount() {
+        let amount = |s: &str| parse_amount(s).map(|d| d.to_string());
+        assert_eq!(amount("1,234"), Ok("1234".to_string()));
+        assert_eq!(amount("￥1,234円"), Ok("1234".to_string()));
+        assert_eq!(amount("-500"), Ok("-500".to_string()));
+        assert_eq!(amount("△500"), Ok("-500".to_string()));
+        assert_eq!(amount("(500)"), Ok("-500".to_string()));
+        assert_eq!(amount("$12.50"), Ok("12.50".to_string()));
+        assert_eq!(amount(""), Ok("0".to_string()));
+        assert!(amount("abc").is_err());
+    }
+
+    #[test]
+    fn test_read_shift_jis_statement_with_split_columns() {
+        let profile: Profile = toml::from_str(
+            r#"
+            encoding = "shift_jis"
+            skip_rows = 1
+            date_column = "取引日"
+            date_format = "%Y/%m/%d"
+            usage_column = "摘要"
+            deposit_column = "お預入れ"
+            withdrawal_column = "お引出し"
+            "#,
+        )
+        .unwrap();
+        let text = "口座番号 1234567\n\
+                    取引日,摘要,お引出し,お預入れ,残高\n\
+                    2024/01/05,カード,\"3,000\",,97000\n\
+                    2024/01/25,給与,,\"200,000\",297000\n";
+        let (bytes, _, _) = encoding_rs::SHIFT_JIS.encode(text);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("statement.csv");
+        std::fs::write(&path, bytes).unwrap();
+
+        let records = profile.read(&path, Path::new("bank.toml")).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                Record::new("2024-01-05".parse().unwrap(), "カード", -3000),
+                Record::new("2024-01-25".parse().unwrap(), "給与", 200000),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_rejects_non_ascii_delimiter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bank.toml");
+        std::fs::write(
+            &path,
+            "delimiter = \"，\"\ndate_column = 1\nusage_column = 2\namount_column = 3\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            Profile::load(&path),
+            Err(Error::InvalidProfile { message, .. }) if message.contains("delimiter")
+        ));
+    }
+
+    #[test]
+    fn test_parse_error_has_line_and_column() {
+        let profile: Profile = toml::from_str(
+            r#"
+            has_header = false
+            date_column = 1
+            usage_column = 2
+            amount_column = 3
+            negate = true
+            "#,
+        )
+        .unwrap();
+        let text = "2024-01-05,本,1500\n2024-01-06,本,x\n";
+        let path = Path::new("statement.csv");
+        let error = profile.parse(text, path, Path::new("p.toml")).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Parse {
+                line: 2,
+                column: 3,
+                ..
+            }
+        ));
+        let records = profile.parse("2024-01-05,本,1500\n", path, Path::new("p.toml"));
+        assert_eq!(records.unwrap()[0].金額, Decimal::from(-1500));
+    }
+}
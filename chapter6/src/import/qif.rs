@@ -0,0 +1,115 @@
+use std::path::Path;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::error::{Error, Result};
+use crate::record::Record;
+
+// QIF の日付は出力するソフトによって書き方が違うので、順に試す
+const DATE_FORMATS: [&str; 6] = [
+    "%Y-%m-%d", "%Y/%m/%d", "%m/%d/%Y", "%m/%d'%y", "%m/%d/%y", "%d.%m.%Y",
+];
+
+// QIF の明細を読む
+// 1行目が項目の種類で、D (日付)、T (金額)、P (相手)、M (メモ)、L (分類) を使う
+// 取引は ^ の行で終わる
+pub fn parse(text: &str, path: &Path) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    let mut transaction = Transaction::default();
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i as u64 + 1;
+        let line = line.trim_end();
+        let Some(code) = line.chars().next() else {
+            continue;
+        };
+        let value = line[code.len_utf8()..].trim();
+        match code {
+            '!' => {}
+            '^' => {
+                if let Some(record) = transaction.finish(path, line_number)? {
+                    records.push(record);
+                }
+                transaction = Transaction::default();
+            }
+            'D' => {
+                transaction.date = Some(parse_date(value).ok_or_else(|| Error::Parse {
+                    path: path.to_path_buf(),
+                    line: line_number,
+                    column: 2,
+                    message: format!("日付 {} を読めません", value),
+                })?);
+            }
+            'T' | 'U' => transaction.amount = Some(super::parse_amount(value, path, line_number)?),
+            'P' => transaction.payee = value.to_string(),
+            'M' => transaction.memo = value.to_string(),
+            'L' => transaction.category = value.to_string(),
+            _ => {}
+        }
+    }
+    Ok(records)
+}
+
+fn parse_date(s: &str) -> Option<NaiveDate> {
+    // 1/ 5'24 のように空白で桁を揃えていることがある
+    let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(&s, format).ok())
+}
+
+#[derive(Default)]
+struct Transaction {
+    date: Option<NaiveDate>,
+    amount: Option<Decimal>,
+    payee: String,
+    memo: String,
+    category: String,
+}
+
+impl Transaction {
+    // 項目のない ^ (種類の行の後など) は読み飛ばす
+    fn finish(self, path: &Path, line: u64) -> Result<Option<Record>> {
+        let (date, amount) = match (self.date, self.amount) {
+            (None, None) => return Ok(None),
+            (Some(date), Some(amount)) => (date, amount),
+            _ => {
+                return Err(Error::Parse {
+                    path: path.to_path_buf(),
+                    line,
+                    column: 0,
+                    message: "日付 (D) か金額 (T) がありません".to_string(),
+                })
+            }
+        };
+        let usage = if self.payee.is_empty() {
+            &self.memo
+        } else {
+            &self.payee
+        };
+        let mut record = Record::new(date, usage, amount);
+        record.分類 = self.category;
+        Ok(Some(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "!Type:Bank\nD01/05/2024\nT-1,500.00\nP書店\nL趣味\n^\n\
+                    D1/25'24\nT200,000.00\nM給料\n^\n";
+        let records = parse(text, Path::new("bank.qif")).unwrap();
+        let mut book = Record::new("2024-01-05".parse().unwrap(), "書店", -1500);
+        book.分類 = "趣味".to_string();
+        assert_eq!(
+            records,
+            [
+                book,
+                Record::new("2024-01-25".parse().unwrap(), "給料", 200000),
+            ]
+        );
+    }
+}
@@ -0,0 +1,512 @@
+// ledger / hledger 形式の仕訳帳 (plain text accounting) との変換
+//
+// monestie の口座は Assets:{口座名} として書き出す。クレジットカードとローンは Liabilities:{口座名} にする
+// 振替でない記録の相手は、分類から Income:{分類} か Expenses:{分類} とする
+//
+//   2024-01-05 書店  ; 趣味:, 本:
+//       Expenses:趣味  1500 JPY
+//       Assets:財布  -1500 JPY
+use std::{collections::BTreeMap, path::Path};
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::error::{Error, Result};
+use crate::ledger::pair_transfers;
+use crate::record::{Record, Tags, DEFAULT_CURRENCY};
+use crate::registry::AccountType;
+
+const ASSETS: &str = "Assets";
+const LIABILITIES: &str = "Liabilities";
+const INCOME: &str = "Income";
+const EXPENSES: &str = "Expenses";
+// 分類のない記録の相手勘定
+const UNCATEGORIZED: &str = "未分類";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub date: NaiveDate,
+    pub description: String,
+    pub tags: Tags,
+    pub postings: Vec<Posting>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Posting {
+    pub account: String,
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+impl Transaction {
+    // 通貨ごとに金額を合計して、全て 0 になるか
+    pub fn is_balanced(&self) -> bool {
+        let mut sums: BTreeMap<&str, Decimal> = BTreeMap::new();
+        for posting in &self.postings {
+            *sums.entry(&posting.currency).or_default() += posting.amount;
+        }
+        sums.values().all(|sum| sum.is_zero())
+    }
+}
+
+// 口座ごとの記録を取引にする。組になった振替は1つの取引にまとめる
+// kind は口座名から口座の種類を返す
+pub fn from_ledger(
+    accounts: &[(String, Vec<Record>)],
+    kind: impl Fn(&str) -> AccountType,
+) -> Vec<Transaction> {
+    let ledger_account = |account: &str| {
+        let parent = if kind(account).is_liability() {
+            LIABILITIES
+        } else {
+            ASSETS
+        };
+        format!("{}:{}", parent, account)
+    };
+    let (pairs, _) = pair_transfers(accounts);
+    let mut paired = vec![Vec::new(); accounts.len()];
+    for (i, (_, records)) in accounts.iter().enumerate() {
+        paired[i] = vec![false; records.len()];
+    }
+    let mut transactions = Vec::new();
+    for &((i, j), (k, l)) in &pairs {
+        paired[i][j] = true;
+        paired[k][l] = true;
+        let (from, record) = (&accounts[i].0, &accounts[i].1[j]);
+        let to = &accounts[k].0;
+        transactions.push(transaction(
+            record,
+            vec![
+                posting(&ledger_account(to), -record.金額, &record.通貨),
+                posting(&ledger_account(from), record.金額, &record.通貨),
+            ],
+        ));
+    }
+    for (i, (account, records)) in accounts.iter().enumerate() {
+        for (j, record) in records.iter().enumerate() {
+            if paired[i][j] {
+                continue;
+            }
+            // 相手のいない振替は、相手の口座への振替としてそのまま書き出す
+            let counter = if !record.振替.is_empty() {
+                ledger_account(&record.振替)
+            } else {
+                let parent = if record.金額 >= Decimal::ZERO {
+                    INCOME
+                } else {
+                    EXPENSES
+                };
+                let category = if record.分類.is_empty() {
+                    UNCATEGORIZED
+                } else {
+                    &record.分類
+                };
+                format!("{}:{}", parent, category)
+            };
+            transactions.push(transaction(
+                record,
+                vec![
+                    posting(&counter, -record.金額, &record.通貨),
+                    posting(&ledger_account(account), record.金額, &record.通貨),
+                ],
+            ));
+        }
+    }
+    transactions.sort_by_key(|t| t.date);
+    transactions
+}
+
+fn transaction(record: &Record, postings: Vec<Posting>) -> Transaction {
+    Transaction {
+        date: record.日付,
+        description: record.用途.clone(),
+        tags: record.タグ.clone(),
+        postings,
+    }
+}
+
+fn posting(account: &str, amount: Decimal, currency: &str) -> Posting {
+    Posting {
+        account: account.to_string(),
+        amount,
+        currency: currency.to_string(),
+    }
+}
+
+// ; から後はコメントとして読まれるので、用途に ; や改行がある取引は書き出さずにエラーにする
+pub fn render(transactions: &[Transaction]) -> Result<String> {
+    let mut out = String::new();
+    for transaction in transactions {
+        if transaction.description.contains([';', '\n', '\r']) {
+            return Err(Error::Journal(format!(
+                "{} の用途 {:?} に ; か改行があります",
+                transaction.date, transaction.description
+            )));
+        }
+        out.push_str(&format!("{} {}", transaction.date, transaction.description));
+        if !transaction.tags.0.is_empty() {
+            let tags: Vec<String> = transaction
+                .tags
+                .0
+                .iter()
+                .map(|t| format!("{}:", t))
+                .collect();
+            out.push_str(&format!("  ; {}", tags.join(", ")));
+        }
+        out.push('\n');
+        for posting in &transaction.postings {
+            out.push_str(&format!(
+                "    {}  {} {}\n",
+                posting.account, posting.amount, posting.currency
+            ));
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+// 仕訳帳を読む
+// 日付で始まる行が取引の見出しで、字下げした行が記帳。それ以外の指示 (account, P など) は読み飛ばす
+// 金額を省略した記帳は1つまで書ける
+pub fn parse(text: &str, path: &Path) -> Result<Vec<Transaction>> {
+    let mut transactions: Vec<(u64, Transaction, Option<usize>)> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i as u64 + 1;
+        let parse_error = |message: String| Error::Parse {
+            path: path.to_path_buf(),
+            line: line_number,
+            column: 0,
+            message,
+        };
+        let (content, comment) = match line.find(';') {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => (line, ""),
+        };
+        if content.trim().is_empty() {
+            // 取引の下のコメントにあるタグも読む
+            if let Some((_, transaction, _)) = transactions.last_mut() {
+                if line.starts_with(char::is_whitespace) {
+                    transaction.tags.0.extend(parse_tags(comment));
+                }
+            }
+            continue;
+        }
+        if line.starts_with(char::is_whitespace) {
+            let Some((_, transaction, elided)) = transactions.last_mut() else {
+                return Err(parse_error("取引の外に記帳があります".to_string()));
+            };
+            let posting = parse_posting(content.trim()).map_err(parse_error)?;
+            match posting {
+                Some(posting) => transaction.postings.push(posting),
+                None if elided.is_none() => {
+                    *elided = Some(transaction.postings.len());
+                    transaction
+                        .postings
+                        .push(posting_placeholder(content.trim()));
+                }
+                None => return Err(parse_error("金額を省略できる記帳は1つだけです".to_string())),
+            }
+        } else if line.starts_with(|c: char| c.is_ascii_digit()) {
+            let (date, description) = parse_header(content).map_err(parse_error)?;
+            transactions.push((
+                line_number,
+                Transaction {
+                    date,
+                    description,
+                    tags: Tags(parse_tags(comment)),
+                    postings: Vec::new(),
+                },
+                None,
+            ));
+        }
+    }
+
+    let mut result = Vec::new();
+    for (line, mut transaction, elided) in transactions {
+        let parse_error = |message: &str| Error::Parse {
+            path: path.to_path_buf(),
+            line,
+            column: 0,
+            message: message.to_string(),
+        };
+        if let Some(i) = elided {
+            let others: Vec<&Posting> = transaction
+                .postings
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, p)| p)
+                .collect();
+            let Some(currency) = others.first().map(|p| p.currency.clone()) else {
+                return Err(parse_error("金額のある記帳がありません"));
+            };
+            if others.iter().any(|p| p.currency != currency) {
+                return Err(parse_error("複数の通貨がある取引では金額を省略できません"));
+            }
+            let sum: Decimal = others.iter().map(|p| p.amount).sum();
+            transaction.postings[i].amount = -sum;
+            transaction.postings[i].currency = currency;
+        }
+        if !transaction.is_balanced() {
+            return Err(parse_error("取引が釣り合っていません"));
+        }
+        result.push(transaction);
+    }
+    Ok(result)
+}
+
+fn parse_header(content: &str) -> std::result::Result<(NaiveDate, String), String> {
+    let content = content.trim();
+    let (date, rest) = content
+        .split_once(char::is_whitespace)
+        .unwrap_or((content, ""));
+    // 2024-01-05=2024-01-10 のような2つ目の日付は使わない
+    let date = date.split('=').next().unwrap_or(date);
+    let date = ["%Y-%m-%d", "%Y/%m/%d", "%Y.%m.%d"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+        .ok_or_else(|| format!("日付 {} を読めません", date))?;
+    let mut rest = rest.trim();
+    // 状態 (* か !) と番号 (括弧) は使わない
+    rest = rest.trim_start_matches(['*', '!']).trim_start();
+    if rest.starts_with('(') {
+        if let Some(end) = rest.find(')') {
+            rest = rest[end + 1..].trim_start();
+        }
+    }
+    Ok((date, rest.to_string()))
+}
+
+// 記帳の行を読む。金額が省略されていれば None を返す
+fn parse_posting(content: &str) -> std::result::Result<Option<Posting>, String> {
+    let content = content.trim_start_matches(['*', '!']).trim_start();
+    // 勘定科目と金額は2つ以上の空白かタブで区切る
+    let split = content
+        .find("  ")
+        .into_iter()
+        .chain(content.find('\t'))
+        .min();
+    let Some(split) = split else {
+        return Ok(None);
+    };
+    let amount = content[split..].trim();
+    if amount.contains(['@', '=']) {
+        return Err("価格 (@) と残高の表明 (=) には対応していません".to_string());
+    }
+    let (amount, currency) = parse_amount(amount)?;
+    Ok(Some(Posting {
+        account: account_name(&content[..split]),
+        amount,
+        currency,
+    }))
+}
+
+fn posting_placeholder(content: &str) -> Posting {
+    Posting {
+        account: account_name(content),
+        amount: Decimal::ZERO,
+        currency: String::new(),
+    }
+}
+
+// 仮想記帳の括弧は外す
+fn account_name(s: &str) -> String {
+    s.trim().trim_matches(['(', ')', '[', ']']).to_string()
+}
+
+// "1500 JPY"、"JPY -1500"、"-$12.34"、"¥1,500" のような金額を読む
+fn parse_amount(s: &str) -> std::result::Result<(Decimal, String), String> {
+    let number: String = s
+        .chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'))
+        .collect();
+    let commodity: String = s
+        .chars()
+        .filter(|c| !c.is_ascii_digit() && !matches!(c, '-' | '+' | '.' | ',' | '"'))
+        .collect();
+    let amount = number
+        .parse()
+        .map_err(|_| format!("金額 {} を読めません", s))?;
+    let currency = match commodity.trim() {
+        "" | "¥" | "￥" | "円" => DEFAULT_CURRENCY.to_string(),
+        "$" => "USD".to_string(),
+        "€" => "EUR".to_string(),
+        "£" => "GBP".to_string(),
+        other => other.to_string(),
+    };
+    Ok((amount, currency))
+}
+
+// "; 旅行:, 友人:" のようなコメントからタグ名を取り出す
+fn parse_tags(comment: &str) -> Vec<String> {
+    comment
+        .split(',')
+        .filter_map(|part| {
+            let (name, _) = part.trim().split_once(':')?;
+            let name = name.trim();
+            (!name.is_empty() && !name.contains(char::is_whitespace)).then(|| name.to_string())
+        })
+        .collect()
+}
+
+// 取引を口座ごとの記録にする
+// Assets: と Liabilities: の勘定を monestie の口座とし、それ以外の勘定は分類として扱う
+// 口座の勘定が2つだけの取引は振替になる
+pub fn to_records(transactions: &[Transaction]) -> BTreeMap<String, Vec<Record>> {
+    let mut accounts: BTreeMap<String, Vec<Record>> = BTreeMap::new();
+    for transaction in transactions {
+        let (own, others): (Vec<&Posting>, Vec<&Posting>) = transaction
+            .postings
+            .iter()
+            .partition(|p| monestie_account(&p.account).is_some());
+        let category = others
+            .first()
+            .and_then(|p| p.account.split_once(':'))
+            .map(|(_, category)| category)
+            .filter(|category| *category != UNCATEGORIZED)
+            .unwrap_or("");
+        for posting in &own {
+            let account = monestie_account(&posting.account).unwrap();
+            let mut record =
+                Record::new(transaction.date, &transaction.description, posting.amount);
+            record.通貨 = posting.currency.clone();
+            record.タグ = transaction.tags.clone();
+            if own.len() == 2 && others.is_empty() {
+                let counterpart = own.iter().find(|p| p.account != posting.account);
+                record.振替 = counterpart
+                    .and_then(|p| monestie_account(&p.account))
+                    .unwrap_or_default()
+                    .to_string();
+            } else {
+                record.分類 = category.to_string();
+            }
+            accounts
+                .entry(account.to_string())
+                .or_default()
+                .push(record);
+        }
+    }
+    accounts
+}
+
+// Liabilities: の勘定になっている口座
+pub fn liability_accounts(transactions: &[Transaction]) -> Vec<String> {
+    let mut accounts: Vec<String> = transactions
+        .iter()
+        .flat_map(|t| &t.postings)
+        .filter_map(|p| p.account.strip_prefix(LIABILITIES)?.strip_prefix(':'))
+        .map(str::to_string)
+        .collect();
+    accounts.sort();
+    accounts.dedup();
+    accounts
+}
+
+fn monestie_account(account: &str) -> Option<&str> {
+    [ASSETS, LIABILITIES].iter().find_map(|parent| {
+        account
+            .strip_prefix(parent)
+            .and_then(|rest| rest.strip_prefix(':'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(date: &str, usage: &str, amount: i32, category: &str, transfer: &str) -> Record {
+        let mut record = Record::new(date.parse().unwrap(), usage, amount);
+        record.分類 = category.to_string();
+        record.振替 = transfer.to_string();
+        record
+    }
+
+    #[test]
+    fn test_export_and_import_round_trip() {
+        let mut book = record("2024-01-05", "書店", -1500, "趣味", "");
+        book.タグ = Tags::from("本;技術書");
+        let accounts = vec![
+            (
+                "財布".to_string(),
+                vec![record("2024-01-03", "引き出し", 10000, "", "銀行"), book],
+            ),
+            (
+                "銀行".to_string(),
+                vec![
+                    record("2024-01-01", "給料", 200000, "給料", ""),
+                    record("2024-01-03", "引き出し", -10000, "", "財布"),
+                ],
+            ),
+        ];
+        let text = render(&from_ledger(&accounts, |_| AccountType::Bank)).unwrap();
+        assert_eq!(
+            text,
+            "2024-01-01 給料\n    Income:給料  -200000 JPY\n    Assets:銀行  200000 JPY\n\n\
+             2024-01-03 引き出し\n    Assets:財布  10000 JPY\n    Assets:銀行  -10000 JPY\n\n\
+             2024-01-05 書店  ; 本:, 技術書:\n    Expenses:趣味  1500 JPY\n    Assets:財布  -1500 JPY\n\n"
+        );
+
+        let transactions = parse(&text, Path::new("ledger.journal")).unwrap();
+        let imported = to_records(&transactions);
+        assert_eq!(imported["財布"], {
+            let mut records = accounts[0].1.clone();
+            records.sort_by_key(|r| r.日付);
+            records
+        });
+        assert_eq!(imported["銀行"], accounts[1].1);
+    }
+
+    #[test]
+    fn test_liabilities_and_invalid_description() {
+        let accounts = vec![(
+            "カード".to_string(),
+            vec![record("2024-02-01", "書店", -3000, "趣味", "")],
+        )];
+        let kind = |_: &str| AccountType::CreditCard;
+        let text = render(&from_ledger(&accounts, kind)).unwrap();
+        assert!(
+            text.contains("    Liabilities:カード  -3000 JPY\n"),
+            "{}",
+            text
+        );
+        let transactions = parse(&text, Path::new("ledger.journal")).unwrap();
+        assert_eq!(to_records(&transactions)["カード"], accounts[0].1);
+        assert_eq!(liability_accounts(&transactions), vec!["カード"]);
+
+        let accounts = vec![(
+            "財布".to_string(),
+            vec![record("2024-02-01", "本; 雑誌", -3000, "趣味", "")],
+        )];
+        assert!(matches!(
+            render(&from_ledger(&accounts, kind)),
+            Err(Error::Journal(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_hledger_syntax() {
+        let text = "; コメント\n\
+                    account Assets:財布\n\
+                    \n\
+                    2024/01/10 * (42) コーヒー ; 外食:\n\
+                    \x20   Expenses:食費:喫茶      $4.50\n\
+                    \x20   Assets:財布\n\
+                    \n\
+                    2024-01-11 合わない取引\n\
+                    \x20   Expenses:食費  500\n\
+                    \x20   Assets:財布  -400\n";
+        let error = parse(text, Path::new("a.journal")).unwrap_err();
+        assert!(matches!(error, Error::Parse { line: 8, .. }));
+
+        let text = text.split("\n\n2024-01-11").next().unwrap();
+        let transactions = parse(text, Path::new("a.journal")).unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].description, "コーヒー");
+        assert_eq!(transactions[0].tags, Tags::from("外食"));
+        assert_eq!(transactions[0].postings[1].amount, "-4.50".parse().unwrap());
+        assert_eq!(transactions[0].postings[1].currency, "USD");
+
+        let records = to_records(&transactions);
+        assert_eq!(records["財布"][0].分類, "食費:喫茶");
+    }
+}
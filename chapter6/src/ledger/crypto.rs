@@ -0,0 +1,171 @@
+use std::{cell::RefCell, collections::HashMap, path::Path};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+
+use crate::error::{Error, Result};
+
+// 暗号化した口座ファイルの形式
+//   先頭 9 バイト: "MONESTIE" と版番号 1
+//   続く 16 バイト: 鍵の導出 (Argon2id) に使う salt
+//   続く 24 バイト: XChaCha20-Poly1305 の nonce
+//   残り: 暗号文と認証タグ
+// 先頭から nonce までは、書き換えられたら分かるように認証の対象 (AAD) にする
+const MAGIC: &[u8; 8] = b"MONESTIE";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+
+// 端末から入力する代わりに、パスフレーズを渡す環境変数
+pub const PASSPHRASE_ENV: &str = "MONESTIE_PASSPHRASE";
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+// パスフレーズから鍵を作って、口座ファイルを暗号化・復号する
+pub struct Cipher {
+    // 暗号化した口座ファイルを初めて読み書きするときに聞く
+    passphrase: RefCell<Option<String>>,
+    // 鍵の導出は遅いので、1回の実行で暗号化するファイルには同じ salt を使い、鍵を使い回す
+    salt: [u8; SALT_LEN],
+    keys: RefCell<HashMap<[u8; SALT_LEN], Key>>,
+}
+
+impl Cipher {
+    pub fn new() -> Self {
+        Self {
+            passphrase: RefCell::new(None),
+            salt: rand::random(),
+            keys: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_passphrase(passphrase: String) -> Self {
+        let cipher = Self::new();
+        cipher.passphrase.replace(Some(passphrase));
+        cipher
+    }
+
+    pub fn has_passphrase(&self) -> bool {
+        self.passphrase.borrow().is_some()
+    }
+
+    pub fn set_passphrase(&self, passphrase: String) {
+        self.passphrase.replace(Some(passphrase));
+    }
+
+    fn key(&self, salt: [u8; SALT_LEN]) -> Result<Key> {
+        if let Some(key) = self.keys.borrow().get(&salt) {
+            return Ok(*key);
+        }
+        let passphrase = match self.passphrase.borrow().clone() {
+            Some(passphrase) => passphrase,
+            None => read_passphrase(false)?,
+        };
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| Error::Crypto(e.to_string()))?;
+        self.passphrase.replace(Some(passphrase));
+        self.keys.borrow_mut().insert(salt, key);
+        Ok(key)
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let mut data = Vec::with_capacity(HEADER_LEN + plaintext.len() + 16);
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.extend_from_slice(&self.salt);
+        data.extend_from_slice(&nonce);
+        let ciphertext = XChaCha20Poly1305::new(&self.key(self.salt)?)
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &data,
+                },
+            )
+            .map_err(|e| Error::Crypto(e.to_string()))?;
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+
+    // パスフレーズが違うか、ファイルが壊れていたら Error::Decrypt にする
+    pub fn decrypt(&self, data: &[u8], path: &Path) -> Result<Vec<u8>> {
+        if data.len() < HEADER_LEN || !is_encrypted(data) || data[MAGIC.len()] != VERSION {
+            return Err(Error::Decrypt(path.to_path_buf()));
+        }
+        let (header, ciphertext) = data.split_at(HEADER_LEN);
+        let salt_start = MAGIC.len() + 1;
+        let salt: [u8; SALT_LEN] = header[salt_start..salt_start + SALT_LEN]
+            .try_into()
+            .unwrap();
+        let nonce = XNonce::from_slice(&header[salt_start + SALT_LEN..]);
+        XChaCha20Poly1305::new(&self.key(salt)?)
+            .decrypt(
+                nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| Error::Decrypt(path.to_path_buf()))
+    }
+}
+
+impl Default for Cipher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 環境変数か端末からパスフレーズを読む
+// confirm なら、打ち間違えないように端末から2回入力してもらう
+pub fn read_passphrase(confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        if !passphrase.is_empty() {
+            return Ok(passphrase);
+        }
+    }
+    let passphrase = rpassword::prompt_password("パスフレーズ: ")?;
+    if passphrase.is_empty() {
+        return Err(Error::Crypto("パスフレーズが空です".to_string()));
+    }
+    if confirm && rpassword::prompt_password("もう一度入力してください: ")? != passphrase
+    {
+        return Err(Error::Crypto("パスフレーズが一致しません".to_string()));
+    }
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decrypt_detects_wrong_passphrase_and_tampering() {
+        let path = Path::new("家計.csv.enc");
+        let cipher = Cipher::with_passphrase("correct horse".to_string());
+        let mut data = cipher.encrypt("日付,用途,金額\n".as_bytes()).unwrap();
+        assert!(is_encrypted(&data));
+        assert_eq!(
+            cipher.decrypt(&data, path).unwrap(),
+            "日付,用途,金額\n".as_bytes()
+        );
+
+        let other = Cipher::with_passphrase("battery staple".to_string());
+        assert!(matches!(other.decrypt(&data, path), Err(Error::Decrypt(_))));
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(matches!(
+            cipher.decrypt(&data, path),
+            Err(Error::Decrypt(_))
+        ));
+    }
+}
@@ -0,0 +1,552 @@
+use std::{
+    cell::Cell,
+    ffi::OsString,
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use csv::{Reader, WriterBuilder};
+
+use super::crypto::{self, Cipher};
+use super::Storage;
+use crate::budget::BUDGET_FILE;
+use crate::currency::RATES_FILE;
+use crate::error::{Error, Result};
+use crate::record::{Record, HEADER, REQUIRED_COLUMNS};
+use crate::recurring::RECURRING_FILE;
+
+const PLAIN_SUFFIX: &str = ".csv";
+const ENCRYPTED_SUFFIX: &str = ".csv.enc";
+// データディレクトリに置く、口座ではない CSV ファイル
+const DATA_FILES: [&str; 3] = [BUDGET_FILE, RECURRING_FILE, RATES_FILE];
+
+// 口座ごとに {dir}/{口座名}.csv へ保存する
+// 暗号化した口座は {dir}/{口座名}.csv.enc に保存する
+pub struct CsvStorage {
+    dir: PathBuf,
+    cipher: Cipher,
+    // 新しく作る口座を暗号化するか
+    encrypt_new: bool,
+    // 暗号化して書き込む前に、パスフレーズを確かめたか
+    passphrase_checked: Cell<bool>,
+}
+
+impl CsvStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            cipher: Cipher::new(),
+            encrypt_new: false,
+            passphrase_checked: Cell::new(false),
+        }
+    }
+
+    pub fn encrypt_new(mut self, encrypt_new: bool) -> Self {
+        self.encrypt_new = encrypt_new;
+        self
+    }
+
+    pub fn cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    fn plain_path(&self, account: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", account, PLAIN_SUFFIX))
+    }
+
+    fn encrypted_path(&self, account: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", account, ENCRYPTED_SUFFIX))
+    }
+
+    // 暗号化したファイルがあればそれを、なければ平文のファイルを使う
+    // どちらもなければ、新しく作るときの設定に従う
+    fn path(&self, account: &str) -> PathBuf {
+        let encrypted = self.encrypted_path(account);
+        let plain = self.plain_path(account);
+        if encrypted.is_file() || (self.encrypt_new && !plain.is_file()) {
+            encrypted
+        } else {
+            plain
+        }
+    }
+
+    // 暗号化した口座の一覧
+    pub fn encrypted_accounts(&self) -> Result<Vec<String>> {
+        let mut accounts = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if let Some(account) = account_name(&path, ENCRYPTED_SUFFIX) {
+                if is_encrypted_file(&path) {
+                    accounts.push(account);
+                }
+            }
+        }
+        accounts.sort();
+        Ok(accounts)
+    }
+
+    // 既に暗号化した口座があれば、同じパスフレーズか確かめるために読んでみる
+    // なければ、打ち間違えないように2回入力してもらう
+    // 違うパスフレーズで書き込むと、口座ごとにパスフレーズが分かれてしまうため
+    pub fn check_passphrase(&self) -> Result<()> {
+        if self.passphrase_checked.get() {
+            return Ok(());
+        }
+        match self.encrypted_accounts()?.first() {
+            Some(account) => {
+                self.load(account)?;
+            }
+            None if !self.cipher.has_passphrase() => {
+                self.cipher.set_passphrase(crypto::read_passphrase(true)?);
+            }
+            None => {}
+        }
+        self.passphrase_checked.set(true);
+        Ok(())
+    }
+
+    // 口座ファイルとして読める名前の一覧。accounts と違って、読めないファイルはエラーにせずに飛ばす
+    // 口座ではない CSV も置いてあるディレクトリ (データディレクトリ以外) を調べるためのもの
+    pub fn account_files(&self) -> Vec<String> {
+        let mut accounts = self.encrypted_accounts().unwrap_or_default();
+        if let Ok(entries) = fs::read_dir(&self.dir) {
+            for path in entries.flatten().map(|entry| entry.path()) {
+                if is_data_file(&path) {
+                    continue;
+                }
+                if let Some(account) = account_name(&path, PLAIN_SUFFIX) {
+                    if read_header(&path).is_ok() {
+                        accounts.push(account);
+                    }
+                }
+            }
+        }
+        accounts.sort();
+        accounts.dedup();
+        accounts
+    }
+
+    // 平文の口座ファイルを暗号化したファイルに置き換える。既に暗号化していれば None を返す
+    // 平文のバックアップも残さないように消す
+    pub fn encrypt_account(&self, account: &str) -> Result<Option<PathBuf>> {
+        let plain = self.plain_path(account);
+        if !plain.is_file() {
+            return self.already_converted(account);
+        }
+        read_file(&plain)?;
+        let encrypted = self.encrypted_path(account);
+        let data = self.cipher.encrypt(&fs::read(&plain)?)?;
+        write_bytes(&encrypted, &data)?;
+        fs::remove_file(&plain)?;
+        remove_if_exists(&with_suffix(&plain, ".bak"))?;
+        Ok(Some(encrypted))
+    }
+
+    // 暗号化した口座ファイルを平文のファイルに戻す。暗号化していなければ None を返す
+    pub fn decrypt_account(&self, account: &str) -> Result<Option<PathBuf>> {
+        let encrypted = self.encrypted_path(account);
+        if !encrypted.is_file() {
+            return self.already_converted(account);
+        }
+        let data = self.cipher.decrypt(&fs::read(&encrypted)?, &encrypted)?;
+        let plain = self.plain_path(account);
+        write_bytes(&plain, &data)?;
+        fs::remove_file(&encrypted)?;
+        remove_if_exists(&with_suffix(&encrypted, ".bak"))?;
+        Ok(Some(plain))
+    }
+
+    fn already_converted(&self, account: &str) -> Result<Option<PathBuf>> {
+        if self.exists(account)? {
+            Ok(None)
+        } else {
+            Err(Error::AccountNotFound(account.to_string()))
+        }
+    }
+
+    // 全ての記録を CSV にして、暗号化する口座なら暗号化する
+    fn encode(&self, path: &Path, records: &[Record]) -> Result<Vec<u8>> {
+        // 記録が空でもヘッダーを書くため、ヘッダーは自分で書き込む
+        let mut writer = WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        writer.write_record(HEADER)?;
+        for record in records {
+            writer.serialize(record)?;
+        }
+        let data = writer.into_inner().map_err(|e| e.into_error())?;
+        if is_encrypted_path(path) {
+            self.check_passphrase()?;
+            self.cipher.encrypt(&data)
+        } else {
+            Ok(data)
+        }
+    }
+
+    // 一時ファイルに書き込んでから rename することで、書き込み途中の状態を残さない
+    fn write_atomic(&self, path: &Path, records: &[Record]) -> Result<()> {
+        write_bytes(path, &self.encode(path, records)?)
+    }
+}
+
+impl Storage for CsvStorage {
+    fn accounts(&self) -> Result<Vec<String>> {
+        let mut accounts = self.encrypted_accounts()?;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if is_data_file(&path) {
+                continue;
+            }
+            if let Some(account) = account_name(&path, PLAIN_SUFFIX) {
+                // 壊れた口座ファイルを飛ばすと exists と食い違うので、エラーにする
+                read_header(&path)?;
+                accounts.push(account);
+            }
+        }
+        accounts.sort();
+        accounts.dedup();
+        Ok(accounts)
+    }
+
+    fn exists(&self, account: &str) -> Result<bool> {
+        Ok(self.plain_path(account).is_file() || self.encrypted_path(account).is_file())
+    }
+
+    fn create(&mut self, account: &str) -> Result<()> {
+        self.save(account, &[])
+    }
+
+    fn load(&self, account: &str) -> Result<Vec<Record>> {
+        let path = self.path(account);
+        if !is_encrypted_path(&path) {
+            return read_file(&path);
+        }
+        let data = self.cipher.decrypt(&fs::read(&path)?, &path)?;
+        read_from(data.as_slice(), &path)
+    }
+
+    fn save(&mut self, account: &str, records: &[Record]) -> Result<()> {
+        self.write_atomic(&self.path(account), records)
+    }
+
+    // 全ての口座の一時ファイルを書き終えてから rename する
+    // 書き込みに失敗したときは、どの口座ファイルも変更しない
+    fn save_all(&mut self, entries: &[(&str, &[Record])]) -> Result<()> {
+        let mut written = Vec::new();
+        for (account, records) in entries {
+            let path = self.path(account);
+            match self
+                .encode(&path, records)
+                .and_then(|data| write_tmp(&path, &data))
+            {
+                Ok(tmp_path) => written.push((tmp_path, path)),
+                Err(e) => {
+                    for (tmp_path, _) in &written {
+                        let _ = fs::remove_file(tmp_path);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        for (tmp_path, path) in written {
+            fs::rename(tmp_path, path)?;
+        }
+        Ok(())
+    }
+
+    // {口座名}.csv.bak (暗号化した口座は {口座名}.csv.enc.bak) にコピーする
+    // 前のバックアップは上書きする
+    fn backup(&self, accounts: &[&str]) -> Result<Vec<PathBuf>> {
+        let mut backups = Vec::new();
+        for account in accounts {
+            let path = self.path(account);
+            let backup = with_suffix(&path, ".bak");
+            fs::copy(&path, &backup)?;
+            backups.push(backup);
+        }
+        Ok(backups)
+    }
+
+    // 暗号化しているかどうかは変えずに、ファイル名を変える
+    fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+        let path = self.path(from);
+        let new_path = if is_encrypted_path(&path) {
+            self.encrypted_path(to)
+        } else {
+            self.plain_path(to)
+        };
+        fs::rename(path, new_path)?;
+        Ok(())
+    }
+}
+
+// {口座名}{suffix} の形のファイル名なら口座名を返す
+fn account_name(path: &Path, suffix: &str) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    name.strip_suffix(suffix)
+        .filter(|account| !account.is_empty() && !account.ends_with(PLAIN_SUFFIX))
+        .map(str::to_string)
+}
+
+fn is_encrypted_path(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(ENCRYPTED_SUFFIX)
+}
+
+fn is_encrypted_file(path: &Path) -> bool {
+    let mut magic = [0; 8];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && crypto::is_encrypted(&magic)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path);
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+// ヘッダーを確かめてから、CSV ファイルの記録を全て読み込む
+pub fn read_file(path: &Path) -> Result<Vec<Record>> {
+    read_from(File::open(path)?, path)
+}
+
+// path はエラーメッセージに使う
+fn read_from(data: impl Read, path: &Path) -> Result<Vec<Record>> {
+    let mut reader = Reader::from_reader(data);
+    let header = reader
+        .headers()
+        .map_err(|e| Error::from_csv(e, path))?
+        .clone();
+    validate_header(&header, path)?;
+    let mut records = Vec::new();
+    for result in reader.records() {
+        let row = result.map_err(|e| Error::from_csv(e, path))?;
+        let record = row.deserialize(Some(&header)).map_err(|e| {
+            let mut error = Error::from_csv(e, path);
+            // 日付と金額は chrono と rust_decimal が読むので、csv のエラーに列番号が入らない
+            if let Error::Parse { column, .. } = &mut error {
+                if *column == 0 {
+                    *column = invalid_column(&header, &row);
+                }
+            }
+            error
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+// 日付か金額の列が読めなければ、その列番号 (1始まり) を返す
+fn invalid_column(header: &csv::StringRecord, row: &csv::StringRecord) -> u64 {
+    let invalid = |name: &str, value: &str| match name {
+        "日付" => value.parse::<chrono::NaiveDate>().is_err(),
+        "金額" => value.parse::<rust_decimal::Decimal>().is_err(),
+        _ => false,
+    };
+    header
+        .iter()
+        .zip(row.iter())
+        .position(|(name, value)| invalid(name, value))
+        .map_or(0, |i| i as u64 + 1)
+}
+
+fn validate_header(header: &csv::StringRecord, path: &Path) -> Result<()> {
+    check_header(header, path, &HEADER[..REQUIRED_COLUMNS], &HEADER)
+}
+
+// required の列が全てあり、allowed 以外の列がないことを確認する
+pub fn check_header(
+    header: &csv::StringRecord,
+    path: &Path,
+    required: &[&str],
+    allowed: &[&str],
+) -> Result<()> {
+    let has_required = required.iter().all(|r| header.iter().any(|h| h == *r));
+    let all_allowed = header.iter().all(|h| allowed.contains(&h));
+    if has_required && all_allowed {
+        Ok(())
+    } else {
+        Err(Error::InvalidHeader {
+            path: path.to_path_buf(),
+            expected: required.join(","),
+            found: header.iter().collect::<Vec<_>>().join(","),
+        })
+    }
+}
+
+fn read_header(path: &Path) -> Result<()> {
+    let mut reader = Reader::from_path(path).map_err(|e| Error::from_csv(e, path))?;
+    let header = reader.headers().map_err(|e| Error::from_csv(e, path))?;
+    validate_header(header, path)
+}
+
+fn is_data_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| DATA_FILES.contains(&name))
+}
+
+// 一時ファイルに書き込んでから rename する
+pub(crate) fn write_bytes(path: &Path, data: &[u8]) -> Result<()> {
+    let tmp_path = write_tmp(path, data)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+// {ファイル名}.tmp に書き込み、そのパスを返す
+fn write_tmp(path: &Path, data: &[u8]) -> Result<PathBuf> {
+    let tmp_path = with_suffix(path, ".tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    Ok(tmp_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::record::Tags;
+
+    fn record(day: u32, usage: &str, amount: i32) -> Record {
+        Record::new(
+            NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            usage,
+            amount,
+        )
+    }
+
+    #[test]
+    fn test_create_append_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = CsvStorage::new(dir.path());
+        storage.create("家計").unwrap();
+        assert!(storage.exists("家計").unwrap());
+        assert!(storage.load("家計").unwrap().is_empty());
+
+        storage
+            .append(
+                "家計",
+                &[record(1, "給料", 300000), record(2, "食費", -3000)],
+            )
+            .unwrap();
+        storage.append("家計", &[record(3, "本", -1500)]).unwrap();
+        assert_eq!(
+            storage.load("家計").unwrap(),
+            vec![
+                record(1, "給料", 300000),
+                record(2, "食費", -3000),
+                record(3, "本", -1500)
+            ]
+        );
+        assert_eq!(storage.accounts().unwrap(), vec!["家計".to_string()]);
+        assert!(!dir.path().join("家計.csv.tmp").exists());
+    }
+
+    #[test]
+    fn test_load_legacy_file_without_category_and_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("古い口座.csv"),
+            "日付,用途,金額\n2024-01-01,給料,300000\n",
+        )
+        .unwrap();
+        let mut storage = CsvStorage::new(dir.path());
+        let mut tagged = record(2, "外食", -4000);
+        tagged.分類 = "食費".to_string();
+        tagged.タグ = Tags::from("旅行;友人");
+        storage.append("古い口座", &[tagged.clone()]).unwrap();
+        assert_eq!(
+            storage.load("古い口座").unwrap(),
+            vec![record(1, "給料", 300000), tagged]
+        );
+        assert!(fs::read_to_string(dir.path().join("古い口座.csv"))
+            .unwrap()
+            .contains("2024-01-02,外食,-4000,食費,旅行;友人"));
+    }
+
+    #[test]
+    fn test_invalid_amount_reports_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("家計.csv");
+        fs::write(
+            &path,
+            "日付,用途,金額\n2024-01-01,給料,1000.5\n2024-01-02,食費,abc\n",
+        )
+        .unwrap();
+        match read_file(&path) {
+            Err(Error::Parse { line, column, .. }) => assert_eq!((line, column), (3, 3)),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_encrypted_account_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let passphrase = || Cipher::with_passphrase("passphrase".to_string());
+        let mut storage = CsvStorage::new(dir.path())
+            .encrypt_new(true)
+            .cipher(passphrase());
+        storage.create("家計").unwrap();
+        storage
+            .append("家計", &[record(1, "給料", 300000)])
+            .unwrap();
+        let encrypted = dir.path().join("家計.csv.enc");
+        assert!(crypto::is_encrypted(&fs::read(&encrypted).unwrap()));
+        assert!(!dir.path().join("家計.csv").exists());
+
+        // 別の実行でも同じパスフレーズなら読める
+        let storage = CsvStorage::new(dir.path()).cipher(passphrase());
+        assert_eq!(storage.accounts().unwrap(), vec!["家計".to_string()]);
+        assert_eq!(
+            storage.load("家計").unwrap(),
+            vec![record(1, "給料", 300000)]
+        );
+
+        let plain = storage.decrypt_account("家計").unwrap().unwrap();
+        assert!(!encrypted.exists());
+        assert_eq!(read_file(&plain).unwrap(), vec![record(1, "給料", 300000)]);
+        assert_eq!(storage.decrypt_account("家計").unwrap(), None);
+        assert_eq!(storage.encrypt_account("家計").unwrap(), Some(encrypted));
+
+        // 違うパスフレーズでは、新しい口座を作らない
+        let mut storage = CsvStorage::new(dir.path())
+            .encrypt_new(true)
+            .cipher(Cipher::with_passphrase("typo".to_string()));
+        assert!(matches!(storage.create("財布"), Err(Error::Decrypt(_))));
+        assert!(!dir.path().join("財布.csv.enc").exists());
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("other.csv"), "date,usage,amount\n").unwrap();
+        fs::write(dir.path().join("家計.csv"), "日付,用途,金額\n").unwrap();
+        let storage = CsvStorage::new(dir.path());
+        assert_eq!(storage.account_files(), vec!["家計".to_string()]);
+        // 予算などのファイルは口座として扱わない
+        fs::write(dir.path().join(BUDGET_FILE), "月,分類,予算\n").unwrap();
+        let storage = CsvStorage::new(dir.path());
+        assert!(matches!(
+            storage.load("other"),
+            Err(Error::InvalidHeader { .. })
+        ));
+        assert!(matches!(
+            storage.accounts(),
+            Err(Error::InvalidHeader { .. })
+        ));
+        fs::remove_file(dir.path().join("other.csv")).unwrap();
+        assert_eq!(storage.accounts().unwrap(), vec!["家計".to_string()]);
+    }
+}
@@ -0,0 +1,581 @@
+// 口座ごとの記録を保存する層
+// 保存先は Storage トレイトで抽象化していて、CSV と SQLite の実装がある
+pub mod crypto;
+pub mod csv;
+pub mod sqlite;
+
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+
+use crate::error::{Error, Result};
+use crate::record::{self, Record};
+use crate::registry::{self, AccountInfo, Registry};
+
+// 口座の記録の保存先
+pub trait Storage {
+    // 口座の一覧
+    fn accounts(&self) -> Result<Vec<String>>;
+    // 口座が存在するか
+    fn exists(&self, account: &str) -> Result<bool>;
+    // 記録が空の口座を作る
+    fn create(&mut self, account: &str) -> Result<()>;
+    // 口座の記録を全て読み込む
+    fn load(&self, account: &str) -> Result<Vec<Record>>;
+    // 口座の記録を全て置き換える。途中で失敗しても元の記録は壊さない
+    fn save(&mut self, account: &str, records: &[Record]) -> Result<()>;
+
+    // 口座の末尾に記録を追加する。全て追加されるか、何も追加されないかのどちらか
+    fn append(&mut self, account: &str, records: &[Record]) -> Result<()> {
+        let mut all = self.load(account)?;
+        all.extend_from_slice(records);
+        self.save(account, &all)
+    }
+
+    // 複数の口座の記録を置き換える。全ての口座が置き換わるか、どの口座も変わらないかのどちらか
+    fn save_all(&mut self, entries: &[(&str, &[Record])]) -> Result<()>;
+
+    // 複数の口座に記録を追加する。全ての口座に追加されるか、どの口座にも追加されないかのどちらか
+    fn append_all(&mut self, entries: &[(&str, &[Record])]) -> Result<()> {
+        let mut all = Vec::new();
+        for (account, records) in entries {
+            let mut records_of_account = self.load(account)?;
+            records_of_account.extend_from_slice(records);
+            all.push((*account, records_of_account));
+        }
+        let all: Vec<(&str, &[Record])> = all.iter().map(|(a, r)| (*a, r.as_slice())).collect();
+        self.save_all(&all)
+    }
+
+    // 書き換える前に口座のバックアップを作り、そのパスを返す
+    fn backup(&self, accounts: &[&str]) -> Result<Vec<PathBuf>>;
+
+    // 口座名を変える。記録の振替の相手の口座名は変えない
+    fn rename(&mut self, from: &str, to: &str) -> Result<()>;
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, ValueEnum)]
+pub enum Backend {
+    /// 口座ごとに {口座名}.csv (暗号化した口座は {口座名}.csv.enc) に保存する
+    Csv,
+    /// 全ての口座を1つの SQLite データベースに保存する
+    Sqlite,
+}
+
+pub struct Ledger {
+    storage: Box<dyn Storage>,
+    registry: Registry,
+    // 複式簿記モードでは、全ての記録に相手勘定 (分類か振替先) が必要
+    double_entry: bool,
+    // 予算などのファイルの相対パスは、このディレクトリから見る
+    data_dir: PathBuf,
+}
+
+impl Ledger {
+    pub fn new(storage: Box<dyn Storage>) -> Self {
+        Self {
+            storage,
+            registry: Registry::default(),
+            double_entry: false,
+            data_dir: PathBuf::new(),
+        }
+    }
+
+    pub fn registry(mut self, registry: Registry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn double_entry(mut self, double_entry: bool) -> Self {
+        self.double_entry = double_entry;
+        self
+    }
+
+    // CSV なら data_dir に口座ファイルを置き、SQLite なら data_dir から見た db_path のデータベースを使う
+    // encrypted なら新しく作る口座を暗号化する。暗号化した口座が既にあるときも暗号化する
+    // 暗号化して書き込む前に、パスフレーズを既にある暗号化した口座で確かめる (CsvStorage::check_passphrase)
+    pub fn open(
+        backend: Backend,
+        data_dir: &Path,
+        db_path: &Path,
+        encrypted: bool,
+    ) -> Result<Self> {
+        std::fs::create_dir_all(data_dir)?;
+        let storage: Box<dyn Storage> = match backend {
+            Backend::Csv => {
+                let storage = csv::CsvStorage::new(data_dir);
+                let encrypted = encrypted || !storage.encrypted_accounts()?.is_empty();
+                Box::new(storage.encrypt_new(encrypted))
+            }
+            Backend::Sqlite if encrypted => {
+                return Err(Error::Crypto(
+                    "SQLite の保存先は暗号化できません".to_string(),
+                ))
+            }
+            Backend::Sqlite => Box::new(sqlite::SqliteStorage::open(&data_dir.join(db_path))?),
+        };
+        let registry = Registry::load(&data_dir.join(registry::REGISTRY_FILE))?;
+        let mut ledger = Self::new(storage).registry(registry);
+        ledger.data_dir = data_dir.to_path_buf();
+        Ok(ledger)
+    }
+
+    // 予算・定期の入出金・為替レートのファイルの場所。相対パスはデータディレクトリから見る
+    pub fn data_file(&self, path: &Path) -> PathBuf {
+        self.data_dir.join(path)
+    }
+
+    pub fn accounts(&self) -> Result<Vec<String>> {
+        self.storage.accounts()
+    }
+
+    // 同じ名前の口座があればエラーにする (上書きはしない)
+    pub fn create_account(&mut self, account: &str, info: AccountInfo) -> Result<()> {
+        if self.storage.exists(account)? {
+            return Err(Error::DuplicateAccount(account.to_string()));
+        }
+        self.storage.create(account)?;
+        self.registry.insert(account, info)
+    }
+
+    pub fn account_info(&self, account: &str) -> AccountInfo {
+        self.registry.get(account)
+    }
+
+    // 口座名を変える。他の口座の振替の相手の口座名も書き換える
+    pub fn rename_account(&mut self, from: &str, to: &str) -> Result<Change> {
+        self.ensure_exists(from)?;
+        if self.storage.exists(to)? {
+            return Err(Error::DuplicateAccount(to.to_string()));
+        }
+        self.storage.rename(from, to)?;
+        self.registry.rename(from, to)?;
+
+        let mut accounts = self.all_records()?;
+        let mut changed = Vec::new();
+        for (account, records) in &mut accounts {
+            for record in records.iter_mut().filter(|r| r.振替 == from) {
+                record.振替 = to.to_string();
+                changed.push((account.clone(), record.clone()));
+            }
+        }
+        if changed.is_empty() {
+            return Ok(Change {
+                records: changed,
+                backups: Vec::new(),
+            });
+        }
+        self.rewrite(&accounts, changed)
+    }
+
+    // 口座を閉じる。記録は残すが、新しい記録は追加できなくなる
+    pub fn close_account(&mut self, account: &str) -> Result<()> {
+        self.ensure_exists(account)?;
+        let mut info = self.registry.get(account);
+        info.closed = true;
+        self.registry.insert(account, info)
+    }
+
+    pub fn records(&self, account: &str) -> Result<Vec<Record>> {
+        self.ensure_exists(account)?;
+        self.storage.load(account)
+    }
+
+    // 全ての口座の記録
+    pub fn all_records(&self) -> Result<Vec<(String, Vec<Record>)>> {
+        let mut accounts = Vec::new();
+        for account in self.accounts()? {
+            let records = self.storage.load(&account)?;
+            accounts.push((account, records));
+        }
+        Ok(accounts)
+    }
+
+    pub fn add(&mut self, account: &str, record: Record) -> Result<()> {
+        self.import(account, &[record])
+    }
+
+    pub fn import(&mut self, account: &str, records: &[Record]) -> Result<()> {
+        self.ensure_open(account)?;
+        self.ensure_balanced(account, records)?;
+        self.storage.append(account, &with_ids(records))
+    }
+
+    // 複数の口座にまとめて記録を追加する。どれかの口座がなければ何も追加しない
+    pub fn add_all(&mut self, entries: &[(&str, &[Record])]) -> Result<()> {
+        let mut with_ids_entries = Vec::new();
+        for (account, records) in entries {
+            self.ensure_open(account)?;
+            self.ensure_balanced(account, records)?;
+            with_ids_entries.push((*account, with_ids(records)));
+        }
+        let entries: Vec<(&str, &[Record])> = with_ids_entries
+            .iter()
+            .map(|(account, records)| (*account, records.as_slice()))
+            .collect();
+        self.storage.append_all(&entries)
+    }
+
+    // 番号のない記録 (番号を導入する前に追加した記録) に番号をつける
+    pub fn assign_ids(&mut self) -> Result<()> {
+        for (account, mut records) in self.all_records()? {
+            if records.iter().all(|r| !r.番号.is_empty()) {
+                continue;
+            }
+            for record in records.iter_mut().filter(|r| r.番号.is_empty()) {
+                record.番号 = record::new_id();
+            }
+            self.storage.save(&account, &records)?;
+        }
+        Ok(())
+    }
+
+    // 番号で指定した記録を書き換える。組になった振替なら、相手の記録の日付・用途・金額・通貨もそろえる
+    // account を指定すれば、その口座の中だけで番号を探す
+    pub fn edit(
+        &mut self,
+        account: Option<&str>,
+        id: &str,
+        edit: impl FnOnce(&mut Record),
+    ) -> Result<Change> {
+        let mut accounts = self.all_records()?;
+        let (i, j) = find(&accounts, account, id)?;
+        let counterpart = counterpart(&accounts, (i, j));
+        edit(&mut accounts[i].1[j]);
+        let edited = accounts[i].1[j].clone();
+        self.ensure_balanced(&accounts[i].0, std::slice::from_ref(&edited))?;
+        let mut changed = vec![(accounts[i].0.clone(), edited.clone())];
+        if let Some((k, l)) = counterpart {
+            let (account, records) = &mut accounts[k];
+            let other = &mut records[l];
+            other.日付 = edited.日付;
+            other.用途 = edited.用途.clone();
+            other.金額 = -edited.金額;
+            other.通貨 = edited.通貨.clone();
+            changed.push((account.clone(), other.clone()));
+        }
+        self.rewrite(&accounts, changed)
+    }
+
+    // 番号で指定した記録を削除する。組になった振替なら、相手の記録も削除する
+    pub fn delete(&mut self, account: Option<&str>, id: &str) -> Result<Change> {
+        let mut accounts = self.all_records()?;
+        let (i, j) = find(&accounts, account, id)?;
+        let counterpart = counterpart(&accounts, (i, j));
+        let mut changed = vec![(accounts[i].0.clone(), accounts[i].1.remove(j))];
+        if let Some((k, l)) = counterpart {
+            changed.push((accounts[k].0.clone(), accounts[k].1.remove(l)));
+        }
+        self.rewrite(&accounts, changed)
+    }
+
+    // changed の記録がある口座をバックアップしてから、まとめて保存する
+    fn rewrite(
+        &mut self,
+        accounts: &[(String, Vec<Record>)],
+        changed: Vec<(String, Record)>,
+    ) -> Result<Change> {
+        let names: Vec<&str> = changed
+            .iter()
+            .map(|(account, _)| account.as_str())
+            .collect();
+        let backups = self.storage.backup(&names)?;
+        let entries: Vec<(&str, &[Record])> = accounts
+            .iter()
+            .filter(|(account, _)| names.contains(&account.as_str()))
+            .map(|(account, records)| (account.as_str(), records.as_slice()))
+            .collect();
+        self.storage.save_all(&entries)?;
+        Ok(Change {
+            records: changed,
+            backups,
+        })
+    }
+
+    // from から to へ振り替える。両方の口座に、相手の口座名をつけた記録を追加する
+    pub fn transfer(&mut self, from: &str, to: &str, record: Record) -> Result<()> {
+        if from == to {
+            return Err(Error::SameAccount(from.to_string()));
+        }
+        self.ensure_open(from)?;
+        self.ensure_open(to)?;
+        let withdrawal = Record {
+            金額: -record.金額,
+            振替: to.to_string(),
+            ..record.clone()
+        };
+        let deposit = Record {
+            振替: from.to_string(),
+            ..record
+        };
+        self.storage.append_all(&[
+            (from, &with_ids(&[withdrawal])),
+            (to, &with_ids(&[deposit])),
+        ])
+    }
+
+    fn ensure_balanced(&self, account: &str, records: &[Record]) -> Result<()> {
+        if !self.double_entry {
+            return Ok(());
+        }
+        match records
+            .iter()
+            .find(|r| r.分類.is_empty() && r.振替.is_empty())
+        {
+            Some(record) => Err(Error::Unbalanced(format!(
+                "{} の {} {} に分類がありません",
+                account, record.日付, record.用途
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn ensure_open(&self, account: &str) -> Result<()> {
+        self.ensure_exists(account)?;
+        if self.registry.get(account).closed {
+            return Err(Error::AccountClosed(account.to_string()));
+        }
+        Ok(())
+    }
+
+    fn ensure_exists(&self, account: &str) -> Result<()> {
+        if self.storage.exists(account)? {
+            Ok(())
+        } else {
+            Err(Error::AccountNotFound(account.to_string()))
+        }
+    }
+}
+
+// 編集・削除した記録と、その前に作ったバックアップ
+pub struct Change {
+    pub records: Vec<(String, Record)>,
+    pub backups: Vec<PathBuf>,
+}
+
+// 番号のない記録に番号をつける
+fn with_ids(records: &[Record]) -> Vec<Record> {
+    records
+        .iter()
+        .map(|record| {
+            let mut record = record.clone();
+            if record.番号.is_empty() {
+                record.番号 = record::new_id();
+            }
+            record
+        })
+        .collect()
+}
+
+// 番号 (先頭の数桁だけでもよい) で記録を探す。account を指定すれば、その口座の中だけで探す
+// 見つからず、探した口座に番号のない記録があれば、assign-ids を案内するエラーにする
+pub fn find(
+    accounts: &[(String, Vec<Record>)],
+    account: Option<&str>,
+    id: &str,
+) -> Result<Position> {
+    if let Some(account) = account {
+        if !accounts.iter().any(|(name, _)| name == account) {
+            return Err(Error::AccountNotFound(account.to_string()));
+        }
+    }
+    let mut found = Vec::new();
+    let mut without_ids = Vec::new();
+    for (i, (name, records)) in accounts.iter().enumerate() {
+        if account.is_some() && account != Some(name.as_str()) {
+            continue;
+        }
+        if records.iter().any(|record| record.番号.is_empty()) {
+            without_ids.push(name.as_str());
+        }
+        for (j, record) in records.iter().enumerate() {
+            if !id.is_empty() && record.番号.starts_with(id) {
+                found.push((i, j));
+            }
+        }
+    }
+    match found.as_slice() {
+        [position] => Ok(*position),
+        [] if !without_ids.is_empty() => Err(Error::MissingIds(without_ids.join(", "))),
+        [] => Err(Error::RecordNotFound(id.to_string())),
+        _ => Err(Error::AmbiguousId(id.to_string())),
+    }
+}
+
+// 組になった振替の相手の記録の位置
+fn counterpart(accounts: &[(String, Vec<Record>)], position: Position) -> Option<Position> {
+    let (pairs, _) = pair_transfers(accounts);
+    pairs.into_iter().find_map(|(a, b)| {
+        if a == position {
+            Some(b)
+        } else if b == position {
+            Some(a)
+        } else {
+            None
+        }
+    })
+}
+
+// accounts の中の記録の位置 (口座の位置, 口座の中の記録の位置)
+pub type Position = (usize, usize);
+
+// 振替の記録を、相手の口座の記録と組にする
+// 戻り値は (出金側, 入金側) の組と、相手が見つからない振替の記録
+pub fn pair_transfers(
+    accounts: &[(String, Vec<Record>)],
+) -> (Vec<(Position, Position)>, Vec<Position>) {
+    let index = |name: &str| accounts.iter().position(|(account, _)| account == name);
+    let mut used = vec![Vec::new(); accounts.len()];
+    for (i, (_, records)) in accounts.iter().enumerate() {
+        used[i] = vec![false; records.len()];
+    }
+    let mut pairs = Vec::new();
+    for (i, (account, records)) in accounts.iter().enumerate() {
+        for (j, record) in records.iter().enumerate() {
+            if record.振替.is_empty() || record.金額 >= Decimal::ZERO {
+                continue;
+            }
+            let Some(k) = index(&record.振替) else {
+                continue;
+            };
+            let counterpart = accounts[k].1.iter().enumerate().position(|(l, other)| {
+                !used[k][l]
+                    && other.振替 == *account
+                    && other.日付 == record.日付
+                    && other.通貨 == record.通貨
+                    && other.金額 == -record.金額
+            });
+            if let Some(l) = counterpart {
+                used[i][j] = true;
+                used[k][l] = true;
+                pairs.push(((i, j), (k, l)));
+            }
+        }
+    }
+    let mut unmatched = Vec::new();
+    for (i, (_, records)) in accounts.iter().enumerate() {
+        for (j, record) in records.iter().enumerate() {
+            if !record.振替.is_empty() && !used[i][j] {
+                unmatched.push((i, j));
+            }
+        }
+    }
+    (pairs, unmatched)
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::*;
+
+    #[test]
+    fn test_transfer_writes_linked_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ledger = Ledger::new(Box::new(csv::CsvStorage::new(dir.path())));
+        ledger
+            .create_account("銀行", AccountInfo::default())
+            .unwrap();
+        ledger
+            .create_account("財布", AccountInfo::default())
+            .unwrap();
+        let record = Record::new("2024-01-10".parse().unwrap(), "引き出し", 10000);
+        ledger.transfer("銀行", "財布", record).unwrap();
+
+        let withdrawal = &ledger.records("銀行").unwrap()[0];
+        assert_eq!(
+            (withdrawal.金額, withdrawal.振替.as_str()),
+            (Decimal::from(-10000), "財布")
+        );
+        let deposit = &ledger.records("財布").unwrap()[0];
+        assert_eq!(
+            (deposit.金額, deposit.振替.as_str()),
+            (Decimal::from(10000), "銀行")
+        );
+
+        let record = Record::new("2024-01-10".parse().unwrap(), "引き出し", 10000);
+        assert!(matches!(
+            ledger.transfer("銀行", "カード", record.clone()),
+            Err(Error::AccountNotFound(_))
+        ));
+        assert!(matches!(
+            ledger.transfer("銀行", "銀行", record),
+            Err(Error::SameAccount(_))
+        ));
+        assert_eq!(ledger.records("銀行").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_edit_and_delete_transfer_by_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ledger = Ledger::new(Box::new(csv::CsvStorage::new(dir.path())));
+        ledger
+            .create_account("銀行", AccountInfo::default())
+            .unwrap();
+        ledger
+            .create_account("財布", AccountInfo::default())
+            .unwrap();
+        let record = Record::new("2024-01-10".parse().unwrap(), "引き出し", 10000);
+        ledger.transfer("銀行", "財布", record).unwrap();
+        let lunch = Record::new("2024-01-11".parse().unwrap(), "昼食", -800);
+        ledger.add("財布", lunch).unwrap();
+
+        let id = ledger.records("銀行").unwrap()[0].番号.clone();
+        assert_eq!(id.len(), 12);
+        let change = ledger
+            .edit(None, &id[..6], |r| r.金額 = Decimal::from(-12000))
+            .unwrap();
+        assert_eq!(change.records.len(), 2);
+        assert!(dir.path().join("財布.csv.bak").exists());
+        assert_eq!(
+            ledger.records("財布").unwrap()[0].金額,
+            Decimal::from(12000)
+        );
+
+        assert!(matches!(
+            ledger.delete(Some("財布"), &id),
+            Err(Error::RecordNotFound(_))
+        ));
+        ledger.delete(Some("銀行"), &id).unwrap();
+        assert!(ledger.records("銀行").unwrap().is_empty());
+        let rest = ledger.records("財布").unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].用途, "昼食");
+        assert!(matches!(
+            ledger.delete(None, &id),
+            Err(Error::RecordNotFound(_))
+        ));
+
+        // 番号をつける前の記録がある口座は、assign-ids を案内する
+        std::fs::write(
+            dir.path().join("古い口座.csv"),
+            "日付,用途,金額\n2024-01-01,給料,300000\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            ledger.delete(None, &id),
+            Err(Error::MissingIds(account)) if account == "古い口座"
+        ));
+        assert!(matches!(
+            ledger.delete(Some("銀行"), &id),
+            Err(Error::RecordNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_double_entry_requires_category() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ledger = Ledger::new(Box::new(csv::CsvStorage::new(dir.path()))).double_entry(true);
+        ledger
+            .create_account("財布", AccountInfo::default())
+            .unwrap();
+        let mut record = Record::new("2024-01-10".parse().unwrap(), "昼食", -800);
+        assert!(matches!(
+            ledger.add("財布", record.clone()),
+            Err(Error::Unbalanced(_))
+        ));
+        record.分類 = "食費".to_string();
+        ledger.add("財布", record).unwrap();
+    }
+}
@@ -0,0 +1,300 @@
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection};
+
+use super::Storage;
+use crate::error::{Error, Result};
+use crate::record::{Record, Tags, DEFAULT_CURRENCY, HEADER, REQUIRED_COLUMNS};
+
+// 全ての口座を1つの SQLite データベースに保存する
+pub struct SqliteStorage {
+    connection: Connection,
+    path: PathBuf,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::init(Connection::open(path)?, path)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?, Path::new(":memory:"))
+    }
+
+    fn init(connection: Connection, path: &Path) -> Result<Self> {
+        connection.execute_batch(
+            "PRAGMA foreign_keys = ON;
+            CREATE TABLE IF NOT EXISTS accounts (
+                name TEXT PRIMARY KEY
+            );
+            CREATE TABLE IF NOT EXISTS records (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                account TEXT NOT NULL REFERENCES accounts(name),
+                日付 TEXT NOT NULL,
+                用途 TEXT NOT NULL,
+                金額 TEXT NOT NULL,
+                分類 TEXT NOT NULL DEFAULT '',
+                タグ TEXT NOT NULL DEFAULT '',
+                振替 TEXT NOT NULL DEFAULT '',
+                通貨 TEXT NOT NULL DEFAULT 'JPY',
+                番号 TEXT NOT NULL DEFAULT ''
+            );",
+        )?;
+        migrate(&connection, path)?;
+        Ok(Self {
+            connection,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+// records テーブルに 日付,用途,金額 の列があるか確認し、古いデータベースに足りない列を追加する
+fn migrate(connection: &Connection, path: &Path) -> Result<()> {
+    let mut statement =
+        connection.prepare("SELECT name, type FROM pragma_table_info('records')")?;
+    let columns = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let (columns, types): (Vec<String>, Vec<String>) = columns.into_iter().unzip();
+    let required = &HEADER[..REQUIRED_COLUMNS];
+    if !required.iter().all(|h| columns.iter().any(|c| c == h)) {
+        return Err(Error::InvalidHeader {
+            path: PathBuf::from(path),
+            expected: required.join(","),
+            found: columns.join(","),
+        });
+    }
+    for column in &HEADER[REQUIRED_COLUMNS..] {
+        if !columns.iter().any(|c| c == column) {
+            let default = if *column == "通貨" {
+                DEFAULT_CURRENCY
+            } else {
+                ""
+            };
+            connection.execute_batch(&format!(
+                "ALTER TABLE records ADD COLUMN {} TEXT NOT NULL DEFAULT '{}'",
+                column, default
+            ))?;
+        }
+    }
+    // 金額が INTEGER の列だと小数が REAL に変換されてしまうので、TEXT の列に作り直す
+    let amount_type = columns
+        .iter()
+        .zip(&types)
+        .find(|(c, _)| *c == "金額")
+        .map(|(_, t)| t.as_str());
+    if amount_type != Some("TEXT") {
+        connection.execute_batch(
+            "BEGIN;
+            ALTER TABLE records ADD COLUMN 金額_text TEXT NOT NULL DEFAULT '0';
+            UPDATE records SET 金額_text = CAST(金額 AS TEXT);
+            ALTER TABLE records DROP COLUMN 金額;
+            ALTER TABLE records RENAME COLUMN 金額_text TO 金額;
+            COMMIT;",
+        )?;
+    }
+    Ok(())
+}
+
+impl Storage for SqliteStorage {
+    fn accounts(&self) -> Result<Vec<String>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT name FROM accounts ORDER BY name")?;
+        let accounts = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(accounts)
+    }
+
+    fn exists(&self, account: &str) -> Result<bool> {
+        let count: i64 = self.connection.query_row(
+            "SELECT COUNT(*) FROM accounts WHERE name = ?1",
+            [account],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    fn create(&mut self, account: &str) -> Result<()> {
+        let tx = self.connection.transaction()?;
+        tx.execute("DELETE FROM records WHERE account = ?1", [account])?;
+        tx.execute(
+            "INSERT OR IGNORE INTO accounts (name) VALUES (?1)",
+            [account],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load(&self, account: &str) -> Result<Vec<Record>> {
+        let mut statement = self.connection.prepare(
+            "SELECT 日付, 用途, 金額, 分類, タグ, 振替, 通貨, 番号 FROM records
+            WHERE account = ?1 ORDER BY id",
+        )?;
+        let records = statement
+            .query_map([account], |row| {
+                Ok(Record {
+                    日付: row.get(0)?,
+                    用途: row.get(1)?,
+                    金額: parse_amount(row.get(2)?)?,
+                    分類: row.get(3)?,
+                    タグ: Tags::from(row.get::<_, String>(4)?.as_str()),
+                    振替: row.get(5)?,
+                    通貨: row.get(6)?,
+                    番号: row.get(7)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(records)
+    }
+
+    fn save(&mut self, account: &str, records: &[Record]) -> Result<()> {
+        let tx = self.connection.transaction()?;
+        tx.execute("DELETE FROM records WHERE account = ?1", [account])?;
+        insert(&tx, account, records)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn append(&mut self, account: &str, records: &[Record]) -> Result<()> {
+        let tx = self.connection.transaction()?;
+        insert(&tx, account, records)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_all(&mut self, entries: &[(&str, &[Record])]) -> Result<()> {
+        let tx = self.connection.transaction()?;
+        for (account, records) in entries {
+            tx.execute("DELETE FROM records WHERE account = ?1", [account])?;
+            insert(&tx, account, records)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn append_all(&mut self, entries: &[(&str, &[Record])]) -> Result<()> {
+        let tx = self.connection.transaction()?;
+        for (account, records) in entries {
+            insert(&tx, account, records)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // データベース全体を {データベース}.bak に書き出す
+    fn backup(&self, _accounts: &[&str]) -> Result<Vec<PathBuf>> {
+        let mut backup = self.path.clone().into_os_string();
+        backup.push(".bak");
+        let backup = PathBuf::from(backup);
+        if backup.exists() {
+            std::fs::remove_file(&backup)?;
+        }
+        self.connection
+            .execute("VACUUM INTO ?1", [backup.to_string_lossy()])?;
+        Ok(vec![backup])
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+        let tx = self.connection.transaction()?;
+        tx.execute("INSERT INTO accounts (name) VALUES (?1)", [to])?;
+        tx.execute(
+            "UPDATE records SET account = ?2 WHERE account = ?1",
+            [from, to],
+        )?;
+        tx.execute("DELETE FROM accounts WHERE name = ?1", [from])?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn insert(connection: &Connection, account: &str, records: &[Record]) -> Result<()> {
+    let mut statement = connection.prepare(
+        "INSERT INTO records (account, 日付, 用途, 金額, 分類, タグ, 振替, 通貨, 番号)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?;
+    for record in records {
+        statement.execute(params![
+            account,
+            record.日付,
+            record.用途,
+            record.金額.to_string(),
+            record.分類,
+            record.タグ.to_string(),
+            record.振替,
+            record.通貨,
+            record.番号
+        ])?;
+    }
+    Ok(())
+}
+
+fn parse_amount(text: String) -> rusqlite::Result<rust_decimal::Decimal> {
+    text.parse().map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn test_create_append_load() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        let mut record = Record::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), "給料", 300000);
+        record.分類 = "収入".to_string();
+        record.タグ = Tags::from("本業");
+        // 小数の金額も誤差なく読み書きできる
+        record.金額 = "1234.50".parse().unwrap();
+        record.通貨 = "USD".to_string();
+        // 存在しない口座には追加できない
+        assert!(storage
+            .append("家計", std::slice::from_ref(&record))
+            .is_err());
+        storage.create("家計").unwrap();
+        storage
+            .append("家計", std::slice::from_ref(&record))
+            .unwrap();
+        storage
+            .append("家計", std::slice::from_ref(&record))
+            .unwrap();
+        assert_eq!(storage.load("家計").unwrap(), vec![record.clone(), record]);
+        assert_eq!(storage.accounts().unwrap(), vec!["家計".to_string()]);
+    }
+
+    #[test]
+    fn test_migrate_adds_missing_columns() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE accounts (name TEXT PRIMARY KEY);
+                CREATE TABLE records (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    account TEXT NOT NULL,
+                    日付 TEXT NOT NULL,
+                    用途 TEXT NOT NULL,
+                    金額 INTEGER NOT NULL
+                );
+                INSERT INTO accounts VALUES ('家計');
+                INSERT INTO records (account, 日付, 用途, 金額)
+                VALUES ('家計', '2024-01-01', '給料', 300000);",
+            )
+            .unwrap();
+        let storage = SqliteStorage::init(connection, Path::new(":memory:")).unwrap();
+        assert_eq!(
+            storage.load("家計").unwrap(),
+            vec![Record::new(
+                NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                "給料",
+                300000
+            )]
+        );
+    }
+}
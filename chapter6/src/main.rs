@@ -1,15 +1,63 @@
-use std::{collections::HashMap, fs::OpenOptions};
+mod balance;
+mod budget;
+mod chart;
+mod config;
+mod currency;
+mod error;
+mod import;
+mod journal;
+mod ledger;
+mod record;
+mod recurring;
+mod registry;
+mod report;
+mod tui;
+
+use std::path::{Path, PathBuf};
 
 use chrono::NaiveDate;
 use clap::{Args, Parser, Subcommand};
-use csv::{Reader, Writer, WriterBuilder};
-use serde::{Deserialize, Serialize};
+use rust_decimal::Decimal;
+
+use config::Config;
+use currency::Rates;
+use error::{Error, Lang, Result};
+use ledger::csv::CsvStorage;
+use ledger::{Backend, Ledger, Storage};
+use record::{Record, Tags, DEFAULT_CURRENCY};
+use registry::{AccountInfo, AccountType};
+use report::{Format, GroupBy, Report};
 
 #[derive(Parser)]
-#[clap(version = "1.0")]
+#[clap(
+    version = "1.0",
+    after_help = "終了コード: 0 成功, 1 入出力エラー, 2 引数エラー, 3 口座や記録が見つからない, \
+                  4 口座が既に存在する, 5 CSV や設定ファイルの内容が不正, 6 データベースエラー, \
+                  7 為替レートがない, 8 複式簿記として釣り合わない, 9 グラフを描けない, \
+                  10 暗号化や復号ができない"
+)]
 struct App {
     #[clap(subcommand)]
     command: Command,
+    /// 保存先
+    #[clap(long, value_enum, default_value_t = Backend::Csv, global = true)]
+    storage: Backend,
+    /// 設定ファイル。省略時は $XDG_CONFIG_HOME/monestie/config.toml
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+    /// 口座ファイルを置くディレクトリ。省略時は設定ファイルの data_dir か $XDG_DATA_HOME/monestie
+    #[clap(long, global = true)]
+    data_dir: Option<PathBuf>,
+    /// SQLite のデータベースファイル (--storage sqlite のとき)。相対パスはデータディレクトリから見る
+    #[clap(long, default_value = "monestie.db", global = true)]
+    db: PathBuf,
+    /// 複式簿記モード。全ての記録に相手勘定 (分類か振替先) を必須にする
+    #[clap(long, global = true)]
+    double_entry: bool,
+    /// 新しく作る口座をパスフレーズで暗号化する。暗号化した口座があれば指定しなくてもよい
+    /// (パスフレーズは端末から入力するか、環境変数 MONESTIE_PASSPHRASE で渡す)
+    #[clap(long, global = true)]
+    encrypted: bool,
 }
 
 #[derive(Subcommand)]
@@ -20,23 +68,132 @@ enum Command {
     Deposit(DepositArgs),
     /// 口座から出金する
     Withdraw(WithdrawArgs),
-    /// CSV からインポートする
+    /// 明細 (CSV, OFX, QIF) からインポートする
     Import(ImportArgs),
     /// レポートを出力する
     Report(ReportArgs),
+    /// 分類ごとの予算と支出を比べる
+    Budget(BudgetArgs),
+    /// 収支や残高のグラフを SVG か PNG に書き出す
+    Chart(ChartArgs),
+    /// 口座から口座へ振り替える
+    Transfer(TransferArgs),
+    /// 全ての口座の残高と純資産を表示する
+    Balance(BalanceArgs),
+    /// 定期の入出金を口座に記録する
+    ApplyRecurring(ApplyRecurringArgs),
+    /// ledger / hledger 形式の仕訳帳に書き出す
+    ExportJournal(ExportJournalArgs),
+    /// ledger / hledger 形式の仕訳帳を取り込む
+    ImportJournal(ImportJournalArgs),
+    /// 振替の相手の記録がそろっているか確かめる
+    Check(CheckArgs),
+    /// 記録を番号つきで一覧表示する
+    List(ListArgs),
+    /// 番号のない記録 (番号を導入する前に追加した記録) に番号をつける
+    AssignIds,
+    /// 番号で指定した記録を書き換える
+    Edit(EditArgs),
+    /// 番号で指定した記録を削除する
+    Delete(DeleteArgs),
+    /// 用途や金額で記録を探す
+    Search(SearchArgs),
+    /// 端末の画面で口座と記録を見たり、記録を追加したりする
+    Tui,
+    /// 口座の一覧の表示、名前の変更、口座を閉じる
+    #[clap(subcommand)]
+    Accounts(AccountsCommand),
+    /// 平文の口座ファイルを暗号化する
+    Encrypt(CryptArgs),
+    /// 暗号化した口座ファイルを平文に戻す
+    Decrypt(CryptArgs),
+}
+
+// 入金・出金の記録につける分類とタグと通貨
+#[derive(Args)]
+struct Labels {
+    /// 分類 (食費、交通費など)
+    #[clap(long)]
+    category: Option<String>,
+    /// タグ (複数指定できる)
+    #[clap(long = "tag")]
+    tags: Vec<String>,
+    /// 通貨 (JPY, USD など)。省略時は口座の通貨
+    #[clap(long)]
+    currency: Option<String>,
+}
+
+impl Labels {
+    fn apply(&self, mut record: Record, info: &AccountInfo) -> Record {
+        record.分類 = self.category.clone().unwrap_or_default();
+        record.タグ = Tags(self.tags.clone());
+        record.通貨 = self
+            .currency
+            .as_deref()
+            .unwrap_or(&info.currency)
+            .to_uppercase();
+        record
+    }
+}
+
+// 集計で使う基準通貨と為替レート
+#[derive(Args)]
+struct Conversion {
+    /// 基準通貨。記録は全てこの通貨に換算して集計する
+    #[clap(long, default_value = DEFAULT_CURRENCY)]
+    base: String,
+    /// 為替レートファイル (日付,通貨ペア,レート の CSV)。相対パスはデータディレクトリから見る
+    #[clap(long, default_value = currency::RATES_FILE)]
+    rates: PathBuf,
+}
+
+impl Conversion {
+    fn base(&self) -> String {
+        self.base.to_uppercase()
+    }
+
+    fn rates(&self, ledger: &Ledger) -> Result<Rates> {
+        Rates::load(&data_file(ledger, &self.rates))
+    }
+
+    // 記録をそれぞれの日付のレートで基準通貨に換算する
+    fn convert(&self, ledger: &Ledger, records: &[Record]) -> Result<Vec<Record>> {
+        self.rates(ledger)?.convert_all(records, &self.base())
+    }
+}
+
+// 入出金の額は正の数で指定する (小数も使える)
+fn parse_amount(s: &str) -> std::result::Result<Decimal, String> {
+    match s.parse::<Decimal>() {
+        Ok(amount) if amount > Decimal::ZERO => Ok(amount),
+        Ok(_) => Err("金額は正の数で指定してください".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
 #[derive(Args)]
 struct NewArgs {
     account_name: String,
+    /// 口座の種類
+    #[clap(long = "type", value_enum, default_value_t = AccountType::Cash)]
+    kind: AccountType,
+    /// 口座の通貨。入出金で --currency を省略したときに使う
+    #[clap(long, default_value = DEFAULT_CURRENCY)]
+    currency: String,
+    /// 最初の記録より前の残高 (ローンなどは負の数)
+    #[clap(long, default_value_t = Decimal::ZERO, allow_hyphen_values = true)]
+    opening_balance: Decimal,
 }
 
 impl NewArgs {
-    fn run(&self) {
-        let file_name = format!("{}.csv", self.account_name);
-        let mut writer = Writer::from_path(file_name).unwrap();
-        writer.write_record(["日付", "用途", "金額"]).unwrap();
-        writer.flush().unwrap();
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let info = AccountInfo {
+            kind: self.kind,
+            currency: self.currency.to_uppercase(),
+            opening_balance: self.opening_balance,
+            closed: false,
+        };
+        ledger.create_account(&self.account_name, info)
     }
 }
 
@@ -45,25 +202,17 @@ struct DepositArgs {
     account_name: String,
     date: NaiveDate,
     usage: String,
-    amount: u32,
+    #[clap(value_parser = parse_amount)]
+    amount: Decimal,
+    #[clap(flatten)]
+    labels: Labels,
 }
 
 impl DepositArgs {
-    fn run(&self) {
-        let open_option = OpenOptions::new()
-            .write(true)
-            .append(true) // 追記モード
-            .open(format!("{}.csv", self.account_name))
-            .unwrap();
-        let mut writer = Writer::from_writer(open_option);
-        writer
-            .write_record(&[
-                self.date.format("%Y-%m-%d").to_string(),
-                self.usage.to_string(),
-                self.amount.to_string(),
-            ])
-            .unwrap();
-        writer.flush().unwrap();
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let record = Record::new(self.date, &self.usage, self.amount);
+        let info = ledger.account_info(&self.account_name);
+        ledger.add(&self.account_name, self.labels.apply(record, &info))
     }
 }
 
@@ -72,87 +221,818 @@ struct WithdrawArgs {
     account_name: String,
     date: NaiveDate,
     usage: String,
-    amount: u32,
+    #[clap(value_parser = parse_amount)]
+    amount: Decimal,
+    #[clap(flatten)]
+    labels: Labels,
 }
 
 impl WithdrawArgs {
-    fn run(&self) {
-        let open_option = OpenOptions::new()
-            .write(true)
-            .append(true) // 追記モード
-            .open(format!("{}.csv", self.account_name))
-            .unwrap();
-        let mut writer = Writer::from_writer(open_option);
-        writer
-            .write_record(&[
-                self.date.format("%Y-%m-%d").to_string(),
-                self.usage.to_string(),
-                format!("-{}", self.amount),
-            ])
-            .unwrap();
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let record = Record::new(self.date, &self.usage, -self.amount);
+        let info = ledger.account_info(&self.account_name);
+        ledger.add(&self.account_name, self.labels.apply(record, &info))
     }
 }
 
 #[derive(Args)]
 struct ImportArgs {
-    src_file_name: String,
+    src_file_name: PathBuf,
     dst_account_name: String,
+    /// 明細の形式
+    #[clap(long, value_enum, default_value_t = import::Format::Auto)]
+    format: import::Format,
+    /// CSV の列や文字コードを指定するインポート設定 (TOML)
+    #[clap(long)]
+    profile: Option<PathBuf>,
+    /// 口座に既にある記録と同じ日付・金額・用途の記録も取り込む
+    #[clap(long)]
+    allow_duplicates: bool,
 }
 
 impl ImportArgs {
-    fn run(&self) {
-        let open_option = OpenOptions::new()
-            .write(true)
-            .append(true) // 追記モード
-            .open(format!("{}.csv", self.dst_account_name))
-            .unwrap();
-        let mut writer = WriterBuilder::new()
-            .has_headers(false)
-            .from_writer(open_option);
-        let mut reader = Reader::from_path(&self.src_file_name).unwrap();
-        for result in reader.deserialize() {
-            let record: Record = result.unwrap();
-            writer.serialize(record).unwrap();
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        // 全ての行を読み込めてから追加する
+        let records = import::read(&self.src_file_name, self.format, self.profile.as_deref())?;
+        let (records, skipped) = if self.allow_duplicates {
+            (records, 0)
+        } else {
+            let existing = ledger.records(&self.dst_account_name)?;
+            import::remove_duplicates(&existing, records)
+        };
+        ledger.import(&self.dst_account_name, &records)?;
+        if skipped > 0 {
+            println!(
+                "{} 件をインポートしました ({} 件は重複のためスキップ)",
+                records.len(),
+                skipped
+            );
+        } else {
+            println!("{} 件をインポートしました", records.len());
         }
+        Ok(())
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct Record {
-    日付: NaiveDate,
-    用途: String,
-    金額: i32,
-}
-
 #[derive(Args)]
 struct ReportArgs {
-    files: Vec<String>,
+    /// 集計する口座。省略時は全ての口座
+    accounts: Vec<String>,
+    /// 集計の単位
+    #[clap(long, value_enum, default_value_t = GroupBy::Month)]
+    group_by: GroupBy,
+    /// この日付以降の記録を集計する
+    #[clap(long)]
+    from: Option<NaiveDate>,
+    /// この日付以前の記録を集計する
+    #[clap(long)]
+    to: Option<NaiveDate>,
+    /// 出力形式
+    #[clap(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+    #[clap(flatten)]
+    conversion: Conversion,
 }
 
 impl ReportArgs {
-    fn run(&self) {
-        let mut map = HashMap::new();
-        for file in &self.files {
-            let mut reader = Reader::from_path(file).unwrap();
-            for result in reader.deserialize() {
-                let record: Record = result.unwrap();
-                let sum = map
-                    .entry(record.日付.format("%Y-%m").to_string())
-                    .or_insert(0);
-                *sum += record.金額;
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let accounts = if self.accounts.is_empty() {
+            ledger.accounts()?
+        } else {
+            self.accounts.clone()
+        };
+        let records = self
+            .conversion
+            .convert(ledger, &load_records(ledger, &accounts)?)?;
+        let report = Report::new(&records, self.group_by, self.from, self.to);
+        print!("{}", report.render(self.format));
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct ChartArgs {
+    /// グラフの種類
+    #[clap(value_enum)]
+    kind: chart::Kind,
+    /// 書き出すファイル (拡張子が .png なら PNG、それ以外は SVG)
+    output: PathBuf,
+    /// 集計する口座。省略時は全ての口座
+    accounts: Vec<String>,
+    /// 集計の単位 (棒グラフと折れ線グラフ)
+    #[clap(long, value_enum, default_value_t = GroupBy::Month)]
+    group_by: GroupBy,
+    /// この日付以降の記録を集計する
+    #[clap(long)]
+    from: Option<NaiveDate>,
+    /// この日付以前の記録を集計する
+    #[clap(long)]
+    to: Option<NaiveDate>,
+    /// 文字を描くフォントファイル (TTF, OTF)。省略時はよくある場所から探す
+    #[clap(long)]
+    font: Option<PathBuf>,
+    #[clap(flatten)]
+    conversion: Conversion,
+}
+
+impl ChartArgs {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let accounts = if self.accounts.is_empty() {
+            ledger.accounts()?
+        } else {
+            self.accounts.clone()
+        };
+        let records = self
+            .conversion
+            .convert(ledger, &load_records(ledger, &accounts)?)?;
+        let data = chart::Data::new(self.kind, &records, self.group_by, self.from, self.to);
+        data.render(&self.output, self.font.as_deref())?;
+        println!("{} に書き出しました", self.output.display());
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct BudgetArgs {
+    /// 集計する口座。省略時は全ての口座
+    accounts: Vec<String>,
+    /// 対象の月 (YYYY-MM)。省略時は支出か予算のある全ての月
+    #[clap(long)]
+    month: Option<String>,
+    /// 予算ファイル (月,分類,予算 の CSV。月を * にすると毎月の予算になる)。相対パスはデータディレクトリから見る
+    #[clap(long, default_value = budget::BUDGET_FILE)]
+    file: PathBuf,
+    #[clap(flatten)]
+    conversion: Conversion,
+}
+
+impl BudgetArgs {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let budgets = budget::load(&data_file(ledger, &self.file))?;
+        let accounts = if self.accounts.is_empty() {
+            ledger.accounts()?
+        } else {
+            self.accounts.clone()
+        };
+        let records = self
+            .conversion
+            .convert(ledger, &load_records(ledger, &accounts)?)?;
+        let lines = budget::compare(&budgets, &records, self.month.as_deref());
+        print!("{}", budget::render(&lines));
+        for line in lines.iter().filter(|l| l.is_over()) {
+            eprintln!(
+                "警告：{} の {} が予算を {} {} 超えています",
+                line.month,
+                line.category,
+                -line.remaining(),
+                self.conversion.base()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct TransferArgs {
+    from_account_name: String,
+    to_account_name: String,
+    date: NaiveDate,
+    #[clap(value_parser = parse_amount)]
+    amount: Decimal,
+    /// 用途
+    #[clap(long, default_value = "振替")]
+    usage: String,
+    #[clap(flatten)]
+    labels: Labels,
+}
+
+impl TransferArgs {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let record = Record::new(self.date, &self.usage, self.amount);
+        let info = ledger.account_info(&self.from_account_name);
+        ledger.transfer(
+            &self.from_account_name,
+            &self.to_account_name,
+            self.labels.apply(record, &info),
+        )
+    }
+}
+
+#[derive(Args)]
+struct BalanceArgs {
+    /// この日付の時点の残高を表示する。省略時は今日
+    #[clap(long)]
+    date: Option<NaiveDate>,
+    #[clap(flatten)]
+    conversion: Conversion,
+}
+
+impl BalanceArgs {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let date = self
+            .date
+            .unwrap_or_else(|| chrono::Local::now().date_naive());
+        let mut accounts = Vec::new();
+        for account in ledger.accounts()? {
+            let mut records = ledger.records(&account)?;
+            records.extend(ledger.account_info(&account).opening_record());
+            accounts.push((account, records));
+        }
+        // 外貨の残高は、記録の日付ではなく date のレートで換算する
+        let base = self.conversion.base();
+        let rates = self.conversion.rates(ledger)?;
+        let balances = balance::compute(&accounts, date, &rates, &base)?;
+        print!("{}", balance::render(&balances, date, &base));
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct ApplyRecurringArgs {
+    /// この日付までの定期の入出金を記録する。省略時は今日
+    #[clap(long)]
+    until: Option<NaiveDate>,
+    /// 定期の入出金の規則を置くディレクトリ。口座ごとに {口座名}.csv (周期,開始,用途,金額,終了,分類,通貨 の CSV) を置く。相対パスはデータディレクトリから見る
+    #[clap(long, default_value = recurring::RECURRING_DIR)]
+    dir: PathBuf,
+}
+
+impl ApplyRecurringArgs {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let until = self
+            .until
+            .unwrap_or_else(|| chrono::Local::now().date_naive());
+        let legacy = ledger.data_file(Path::new(recurring::RECURRING_FILE));
+        if legacy.exists() {
+            eprintln!(
+                "警告：{} は読みません。口座ごとに分けて {}/{{口座名}}.csv に移してください",
+                legacy.display(),
+                recurring::RECURRING_DIR
+            );
+        }
+        let dir = data_file(ledger, &self.dir);
+        let rules = recurring::load_dir(&dir)?;
+        let applied_path = dir.join(recurring::APPLIED_FILE);
+        let mut applied = recurring::Applied::load(&applied_path)?;
+        // 前に記録した日付より後の分だけを作る
+        // 記録した日付を保存する前に作った記録と二重にならないよう、既にある記録も除く
+        let mut entries = Vec::new();
+        for (account, records) in recurring::due(&rules, &applied, until) {
+            let existing = ledger.records(&account)?;
+            let (records, _) = import::remove_duplicates(&existing, records);
+            if !records.is_empty() {
+                entries.push((account, records));
+            }
+        }
+        let batch: Vec<(&str, &[Record])> = entries
+            .iter()
+            .map(|(account, records)| (account.as_str(), records.as_slice()))
+            .collect();
+        ledger.add_all(&batch)?;
+        if !rules.is_empty() {
+            applied.mark(&rules, until);
+            applied.save(&applied_path)?;
+        }
+        for (account, records) in &entries {
+            println!("{}: {} 件を記録しました", account, records.len());
+        }
+        if entries.is_empty() {
+            println!("記録する定期の入出金はありません");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct ExportJournalArgs {
+    /// 書き出す口座。省略時は全ての口座
+    accounts: Vec<String>,
+    /// 出力先。省略時は標準出力
+    #[clap(long)]
+    output: Option<PathBuf>,
+}
+
+impl ExportJournalArgs {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let accounts = if self.accounts.is_empty() {
+            ledger.accounts()?
+        } else {
+            self.accounts.clone()
+        };
+        let mut records = Vec::new();
+        for account in accounts {
+            let account_records = ledger.records(&account)?;
+            records.push((account, account_records));
+        }
+        let transactions =
+            journal::from_ledger(&records, |account| ledger.account_info(account).kind);
+        let text = journal::render(&transactions)?;
+        match &self.output {
+            Some(path) => std::fs::write(path, text)?,
+            None => print!("{}", text),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct ImportJournalArgs {
+    src_file_name: PathBuf,
+    /// 仕訳帳にある口座がなければ作る
+    #[clap(long)]
+    create_accounts: bool,
+    /// 口座に既にある記録と同じ日付・金額・用途の記録も取り込む
+    #[clap(long)]
+    allow_duplicates: bool,
+}
+
+impl ImportJournalArgs {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let text = std::fs::read_to_string(&self.src_file_name)?;
+        let transactions = journal::parse(&text, &self.src_file_name)?;
+        let existing = ledger.accounts()?;
+        let liabilities = journal::liability_accounts(&transactions);
+        let mut entries = Vec::new();
+        let mut skipped = 0;
+        for (account, records) in journal::to_records(&transactions) {
+            if !existing.contains(&account) && self.create_accounts {
+                // 負債の勘定から作る口座は、クレジットカードとして作る
+                let kind = if liabilities.contains(&account) {
+                    AccountType::CreditCard
+                } else {
+                    AccountType::default()
+                };
+                let info = AccountInfo {
+                    kind,
+                    ..AccountInfo::default()
+                };
+                ledger.create_account(&account, info)?;
+            }
+            let (records, n) = if self.allow_duplicates || !existing.contains(&account) {
+                (records, 0)
+            } else {
+                import::remove_duplicates(&ledger.records(&account)?, records)
+            };
+            skipped += n;
+            entries.push((account, records));
+        }
+        let batch: Vec<(&str, &[Record])> = entries
+            .iter()
+            .map(|(account, records)| (account.as_str(), records.as_slice()))
+            .collect();
+        ledger.add_all(&batch)?;
+        for (account, records) in &entries {
+            println!("{}: {} 件をインポートしました", account, records.len());
+        }
+        if skipped > 0 {
+            println!("{} 件は重複のためスキップしました", skipped);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct CheckArgs {}
+
+impl CheckArgs {
+    fn run(&self, ledger: &mut Ledger, double_entry: bool) -> Result<()> {
+        let mut accounts = Vec::new();
+        for account in ledger.accounts()? {
+            let records = ledger.records(&account)?;
+            accounts.push((account, records));
+        }
+        let mut problems = Vec::new();
+        let (_, unmatched) = ledger::pair_transfers(&accounts);
+        for (i, j) in unmatched {
+            let (account, record) = (&accounts[i].0, &accounts[i].1[j]);
+            problems.push(format!(
+                "{}: {} {} {} の振替の相手の記録が {} にありません",
+                account, record.日付, record.用途, record.金額, record.振替
+            ));
+        }
+        if double_entry {
+            for (account, records) in &accounts {
+                for record in records {
+                    if record.分類.is_empty() && record.振替.is_empty() {
+                        problems.push(format!(
+                            "{}: {} {} {} に分類がありません",
+                            account, record.日付, record.用途, record.金額
+                        ));
+                    }
+                }
+            }
+        }
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        if problems.is_empty() {
+            println!("問題はありません");
+            Ok(())
+        } else {
+            Err(Error::Unbalanced(format!(
+                "{} 件の問題があります",
+                problems.len()
+            )))
+        }
+    }
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// 表示する口座。省略時は全ての口座
+    accounts: Vec<String>,
+}
+
+impl ListArgs {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let records = records_with_account(ledger, &self.accounts)?;
+        print_records(&records);
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct EditArgs {
+    /// 記録の番号 (先頭の数桁だけでもよい)
+    id: String,
+    /// 記録のある口座。指定するとその口座の中だけで番号を探す
+    #[clap(long)]
+    account: Option<String>,
+    #[clap(long)]
+    date: Option<NaiveDate>,
+    #[clap(long)]
+    usage: Option<String>,
+    /// 金額 (出金は負の数)
+    #[clap(long, allow_hyphen_values = true)]
+    amount: Option<Decimal>,
+    #[clap(long)]
+    category: Option<String>,
+    /// タグ (指定すると全て置き換える)
+    #[clap(long = "tag")]
+    tags: Vec<String>,
+    #[clap(long)]
+    currency: Option<String>,
+}
+
+impl EditArgs {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let change = ledger.edit(self.account.as_deref(), &self.id, |record| {
+            if let Some(date) = self.date {
+                record.日付 = date;
+            }
+            if let Some(usage) = &self.usage {
+                record.用途 = usage.clone();
+            }
+            if let Some(amount) = self.amount {
+                record.金額 = amount;
+            }
+            if let Some(category) = &self.category {
+                record.分類 = category.clone();
+            }
+            if !self.tags.is_empty() {
+                record.タグ = Tags(self.tags.clone());
+            }
+            if let Some(currency) = &self.currency {
+                record.通貨 = currency.to_uppercase();
+            }
+        })?;
+        print_change("変更しました", &change);
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct DeleteArgs {
+    /// 記録の番号 (先頭の数桁だけでもよい)
+    id: String,
+    /// 記録のある口座。指定するとその口座の中だけで番号を探す
+    #[clap(long)]
+    account: Option<String>,
+}
+
+impl DeleteArgs {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let change = ledger.delete(self.account.as_deref(), &self.id)?;
+        print_change("削除しました", &change);
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct SearchArgs {
+    /// 探す口座。省略時は全ての口座
+    accounts: Vec<String>,
+    /// 用途に含まれる文字列 (大文字と小文字は区別しない)
+    #[clap(long)]
+    usage: Option<String>,
+    /// 金額の絶対値の下限
+    #[clap(long)]
+    min: Option<Decimal>,
+    /// 金額の絶対値の上限
+    #[clap(long)]
+    max: Option<Decimal>,
+}
+
+impl SearchArgs {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        let records: Vec<(String, Record)> = records_with_account(ledger, &self.accounts)?
+            .into_iter()
+            .filter(|(_, record)| self.matches(record))
+            .collect();
+        print_records(&records);
+        Ok(())
+    }
+}
+
+impl SearchArgs {
+    fn matches(&self, record: &Record) -> bool {
+        if let Some(usage) = &self.usage {
+            if !record.用途.to_lowercase().contains(&usage.to_lowercase()) {
+                return false;
             }
         }
-        println!("{:?}", map);
+        let amount = record.金額.abs();
+        if self.min.is_some_and(|min| amount < min) {
+            return false;
+        }
+        if self.max.is_some_and(|max| amount > max) {
+            return false;
+        }
+        true
     }
 }
 
+#[derive(Subcommand)]
+enum AccountsCommand {
+    /// 口座の一覧を表示する
+    List {
+        /// 閉じた口座も表示する
+        #[clap(long)]
+        all: bool,
+    },
+    /// 口座名を変える。他の口座の振替の相手の口座名も書き換える
+    Rename { from: String, to: String },
+    /// 口座を閉じる。記録は残るが、新しい記録は追加できなくなる
+    Close { account_name: String },
+}
+
+impl AccountsCommand {
+    fn run(&self, ledger: &mut Ledger) -> Result<()> {
+        match self {
+            AccountsCommand::List { all } => {
+                let mut rows = Vec::new();
+                for account in ledger.accounts()? {
+                    let info = ledger.account_info(&account);
+                    if info.closed && !all {
+                        continue;
+                    }
+                    let balance = info.balance(&ledger.records(&account)?);
+                    rows.push(vec![
+                        account,
+                        info.kind.label().to_string(),
+                        info.currency.clone(),
+                        info.opening_balance.to_string(),
+                        balance.to_string(),
+                        if info.closed { "閉鎖" } else { "" }.to_string(),
+                    ]);
+                }
+                print!(
+                    "{}",
+                    report::format_table(
+                        &["口座", "種類", "通貨", "開始残高", "残高", "状態"],
+                        &rows,
+                        None
+                    )
+                );
+            }
+            AccountsCommand::Rename { from, to } => {
+                let change = ledger.rename_account(from, to)?;
+                println!("口座 {} を {} に変えました", from, to);
+                print_change("変更しました", &change);
+            }
+            AccountsCommand::Close { account_name } => {
+                let info = ledger.account_info(account_name);
+                let balance = info.balance(&ledger.records(account_name)?);
+                ledger.close_account(account_name)?;
+                println!("口座 {} を閉じました", account_name);
+                if !balance.is_zero() {
+                    eprintln!(
+                        "警告：口座 {} の残高が {} {} 残っています",
+                        account_name, balance, info.currency
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct CryptArgs {
+    /// 対象の口座。省略時は全ての口座
+    accounts: Vec<String>,
+}
+
+impl CryptArgs {
+    fn accounts(&self, storage: &CsvStorage) -> Result<Vec<String>> {
+        if self.accounts.is_empty() {
+            storage.accounts()
+        } else {
+            Ok(self.accounts.clone())
+        }
+    }
+
+    fn encrypt(&self, data_dir: &Path) -> Result<()> {
+        let storage = CsvStorage::new(data_dir);
+        storage.check_passphrase()?;
+        for account in self.accounts(&storage)? {
+            if let Some(path) = storage.encrypt_account(&account)? {
+                println!("{}: {} に暗号化しました", account, path.display());
+            }
+        }
+        Ok(())
+    }
+
+    fn decrypt(&self, data_dir: &Path) -> Result<()> {
+        let storage = CsvStorage::new(data_dir);
+        for account in self.accounts(&storage)? {
+            if let Some(path) = storage.decrypt_account(&account)? {
+                println!("{}: {} に復号しました", account, path.display());
+            }
+        }
+        Ok(())
+    }
+}
+
+// 口座名つきで記録を読み込む。accounts が空なら全ての口座
+fn records_with_account(ledger: &Ledger, accounts: &[String]) -> Result<Vec<(String, Record)>> {
+    let accounts = if accounts.is_empty() {
+        ledger.accounts()?
+    } else {
+        accounts.to_vec()
+    };
+    let mut records = Vec::new();
+    for account in accounts {
+        for record in ledger.records(&account)? {
+            records.push((account.clone(), record));
+        }
+    }
+    Ok(records)
+}
+
+fn format_records(records: &[(String, Record)]) -> String {
+    let rows: Vec<Vec<String>> = records
+        .iter()
+        .map(|(account, r)| {
+            vec![
+                r.番号.clone(),
+                account.clone(),
+                r.日付.to_string(),
+                r.用途.clone(),
+                r.金額.to_string(),
+                r.通貨.clone(),
+                r.分類.clone(),
+                r.タグ.to_string(),
+            ]
+        })
+        .collect();
+    report::format_table(
+        &[
+            "番号", "口座", "日付", "用途", "金額", "通貨", "分類", "タグ",
+        ],
+        &rows,
+        None,
+    )
+}
+
+// 予算・定期の入出金・為替レートのファイルは、データディレクトリから探す
+// 以前はカレントディレクトリから探していたので、移し忘れたファイルがあれば知らせる
+fn data_file(ledger: &Ledger, path: &Path) -> PathBuf {
+    let resolved = ledger.data_file(path);
+    if path.is_relative() && !resolved.exists() && path.exists() {
+        eprintln!(
+            "警告：{} は読みません。データディレクトリの {} に移してください",
+            path.display(),
+            resolved.display()
+        );
+    }
+    resolved
+}
+
+// データディレクトリを使う前は、口座ファイルを現在のディレクトリに置いていた
+// 残っている口座ファイルは読まないので、移すように案内する
+fn warn_accounts_in_current_dir(data_dir: &Path) {
+    let current = Path::new(".");
+    let same_dir = match (current.canonicalize(), data_dir.canonicalize()) {
+        (Ok(current), Ok(data_dir)) => current == data_dir,
+        _ => false,
+    };
+    if same_dir {
+        return;
+    }
+    let data = CsvStorage::new(data_dir);
+    let accounts: Vec<String> = CsvStorage::new(current)
+        .account_files()
+        .into_iter()
+        .filter(|account| !data.exists(account).unwrap_or(false))
+        .collect();
+    if !accounts.is_empty() {
+        eprintln!(
+            "警告：現在のディレクトリの口座ファイル ({}) は読みません。データディレクトリ {} に移すか、--data-dir . を指定してください",
+            accounts.join(", "),
+            data_dir.display()
+        );
+    }
+}
+
+// 番号のない記録は edit や delete で指定できないので、番号のつけ方を案内する
+fn print_records(records: &[(String, Record)]) {
+    print!("{}", format_records(records));
+    if records.iter().any(|(_, record)| record.番号.is_empty()) {
+        println!(
+            "番号のない記録があります。assign-ids で番号をつけると、edit や delete で指定できます"
+        );
+    }
+}
+
+fn print_change(action: &str, change: &ledger::Change) {
+    for (account, record) in &change.records {
+        println!(
+            "{}: {} {} {} {} を{}",
+            account, record.番号, record.日付, record.用途, record.金額, action
+        );
+    }
+    for backup in &change.backups {
+        println!("バックアップ: {}", backup.display());
+    }
+}
+
+// 集計する口座の記録をまとめて読み込む
+// 集計する口座どうしの振替は、収入にも支出にも数えないように除く
+fn load_records(ledger: &Ledger, accounts: &[String]) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    for account in accounts {
+        records.extend(
+            ledger
+                .records(account)?
+                .into_iter()
+                .filter(|r| !accounts.contains(&r.振替)),
+        );
+    }
+    Ok(records)
+}
+
 fn main() {
     let args = App::parse();
+    if let Err(e) = run(args) {
+        eprintln!("{}", e.message(Lang::detect()));
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run(args: App) -> Result<()> {
+    let config = match args.config.clone().or_else(Config::default_path) {
+        Some(path) => Config::load(&path)?,
+        None => Config::default(),
+    };
+    let data_dir = args.data_dir.clone().unwrap_or_else(|| config.data_dir());
+    // 暗号化と復号は、口座ファイルをそのまま置き換える
+    match (&args.command, args.storage) {
+        (Command::Encrypt(_) | Command::Decrypt(_), Backend::Sqlite) => {
+            return Err(Error::Crypto(
+                "SQLite の保存先は暗号化できません".to_string(),
+            ))
+        }
+        (Command::Encrypt(args), Backend::Csv) => return args.encrypt(&data_dir),
+        (Command::Decrypt(args), Backend::Csv) => return args.decrypt(&data_dir),
+        _ => {}
+    }
+    if args.storage == Backend::Csv {
+        warn_accounts_in_current_dir(&data_dir);
+    }
+    let mut ledger = Ledger::open(args.storage, &data_dir, &args.db, args.encrypted)?
+        .double_entry(args.double_entry);
     match args.command {
-        Command::New(args) => args.run(),
-        Command::Deposit(args) => args.run(),
-        Command::Withdraw(args) => args.run(),
-        Command::Import(args) => args.run(),
-        Command::Report(args) => args.run(),
+        Command::New(args) => args.run(&mut ledger),
+        Command::Deposit(args) => args.run(&mut ledger),
+        Command::Withdraw(args) => args.run(&mut ledger),
+        Command::Import(args) => args.run(&mut ledger),
+        Command::Report(args) => args.run(&mut ledger),
+        Command::Budget(args) => args.run(&mut ledger),
+        Command::Chart(args) => args.run(&mut ledger),
+        Command::Transfer(args) => args.run(&mut ledger),
+        Command::Balance(args) => args.run(&mut ledger),
+        Command::ApplyRecurring(args) => args.run(&mut ledger),
+        Command::ExportJournal(args) => args.run(&mut ledger),
+        Command::ImportJournal(args) => args.run(&mut ledger),
+        Command::Check(check) => check.run(&mut ledger, args.double_entry),
+        Command::List(args) => args.run(&mut ledger),
+        Command::AssignIds => ledger.assign_ids(),
+        Command::Edit(args) => args.run(&mut ledger),
+        Command::Delete(args) => args.run(&mut ledger),
+        Command::Search(args) => args.run(&mut ledger),
+        Command::Tui => tui::run(&mut ledger),
+        Command::Accounts(command) => command.run(&mut ledger),
+        Command::Encrypt(_) | Command::Decrypt(_) => unreachable!(),
     }
 }
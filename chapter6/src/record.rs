@@ -0,0 +1,93 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// 口座ファイルのヘッダー
+// 先頭の 日付,用途,金額 は必須で、それ以降の列は古いファイルにはないことがある
+pub const HEADER: [&str; 8] = [
+    "日付", "用途", "金額", "分類", "タグ", "振替", "通貨", "番号",
+];
+pub const REQUIRED_COLUMNS: usize = 3;
+// 通貨の列がない古い記録は円とみなす
+pub const DEFAULT_CURRENCY: &str = "JPY";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub 日付: NaiveDate,
+    pub 用途: String,
+    pub 金額: Decimal,
+    #[serde(default)]
+    pub 分類: String,
+    #[serde(default)]
+    pub タグ: Tags,
+    // 振替の記録なら相手の口座名。振替でなければ空
+    #[serde(default)]
+    pub 振替: String,
+    // ISO 4217 の通貨コード (JPY, USD など)
+    #[serde(default = "default_currency")]
+    pub 通貨: String,
+    // 記録を指すための番号。口座に追加するときにつける
+    #[serde(default)]
+    pub 番号: String,
+}
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
+
+impl Record {
+    pub fn new(日付: NaiveDate, 用途: &str, 金額: impl Into<Decimal>) -> Self {
+        Self {
+            日付,
+            用途: 用途.to_string(),
+            金額: 金額.into(),
+            分類: String::new(),
+            タグ: Tags::default(),
+            振替: String::new(),
+            通貨: default_currency(),
+            番号: String::new(),
+        }
+    }
+}
+
+// 記録の番号を作る。12桁の16進数で、編集や削除では先頭の数桁だけでも指定できる
+pub fn new_id() -> String {
+    format!("{:012x}", rand::random::<u64>() >> 16)
+}
+
+// 自由につけられるタグ。CSV では ; 区切りの1列で保存する
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags(pub Vec<String>);
+
+impl fmt::Display for Tags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join(";"))
+    }
+}
+
+impl From<&str> for Tags {
+    fn from(s: &str) -> Self {
+        Tags(
+            s.split(';')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(String::from)
+                .collect(),
+        )
+    }
+}
+
+impl Serialize for Tags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Tags {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(Tags::from(s.as_str()))
+    }
+}
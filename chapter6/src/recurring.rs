@@ -0,0 +1,418 @@
+use std::{collections::BTreeMap, fmt, fs, path::Path, str::FromStr};
+
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use csv::Reader;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::ledger::csv::{check_header, write_bytes};
+use crate::record::{Record, Tags, DEFAULT_CURRENCY};
+
+// 定期の入出金の規則は、口座ごとに {データディレクトリ}/recurring/{口座名}.csv に置く
+pub const RECURRING_DIR: &str = "recurring";
+// 以前の、全ての口座の規則を口座の列つきでまとめたファイル
+pub const RECURRING_FILE: &str = "recurring.csv";
+// 終了、分類、通貨の列は省略できる
+const RECURRING_HEADER: [&str; 7] = ["周期", "開始", "用途", "金額", "終了", "分類", "通貨"];
+const REQUIRED_COLUMNS: usize = 4;
+// 規則ごとに、どの日付まで記録したかを保存するファイル。RECURRING_DIR の中に置く
+pub const APPLIED_FILE: &str = "applied.toml";
+// 定期の記録につけるタグ
+pub const TAG: &str = "定期";
+
+// 定期的な入出金の周期
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Schedule {
+    // monthly:25 (毎月25日。月末より後なら月末)
+    Monthly(u32),
+    // weekly:mon (毎週月曜日)
+    Weekly(Weekday),
+    // yearly:04-01 (毎年4月1日)
+    Yearly(u32, u32),
+    // last-business-day (毎月の最後の平日。祝日は考えない)
+    LastBusinessDay,
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("周期 {} を読めません", s);
+        let (kind, value) = s.split_once(':').unwrap_or((s, ""));
+        match kind {
+            "monthly" => match value.parse() {
+                Ok(day) if (1..=31).contains(&day) => Ok(Schedule::Monthly(day)),
+                _ => Err(invalid()),
+            },
+            "weekly" => value.parse().map(Schedule::Weekly).map_err(|_| invalid()),
+            "yearly" => {
+                // 2月29日のような日付も受け付けるため、閏年で確かめる
+                let date = NaiveDate::parse_from_str(&format!("2000-{}", value), "%Y-%m-%d")
+                    .map_err(|_| invalid())?;
+                Ok(Schedule::Yearly(date.month(), date.day()))
+            }
+            "last-business-day" if value.is_empty() => Ok(Schedule::LastBusinessDay),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+// FromStr で読める形に戻す
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Monthly(day) => write!(f, "monthly:{}", day),
+            Schedule::Weekly(weekday) => {
+                write!(f, "weekly:{}", weekday.to_string().to_lowercase())
+            }
+            Schedule::Yearly(month, day) => write!(f, "yearly:{:02}-{:02}", month, day),
+            Schedule::LastBusinessDay => write!(f, "last-business-day"),
+        }
+    }
+}
+
+impl Schedule {
+    // from..=to の間で、この周期に当たる日付
+    pub fn occurrences(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        if let Schedule::Weekly(weekday) = self {
+            let offset =
+                (7 + weekday.num_days_from_monday() - from.weekday().num_days_from_monday()) % 7;
+            return std::iter::successors(from.checked_add_days(Days::new(offset as u64)), |d| {
+                d.checked_add_days(Days::new(7))
+            })
+            .take_while(|d| *d <= to)
+            .collect();
+        }
+        let mut dates = Vec::new();
+        let mut month = from.with_day(1).unwrap();
+        while month <= to {
+            if let Some(date) = self.in_month(month) {
+                if from <= date && date <= to {
+                    dates.push(date);
+                }
+            }
+            month = month + Months::new(1);
+        }
+        dates
+    }
+
+    // first_day の月の中で、この周期に当たる日付
+    fn in_month(&self, first_day: NaiveDate) -> Option<NaiveDate> {
+        let last_day = first_day + Months::new(1) - Days::new(1);
+        match *self {
+            Schedule::Monthly(day) => first_day.with_day(day.min(last_day.day())),
+            Schedule::Yearly(month, day) if month == first_day.month() => {
+                first_day.with_day(day.min(last_day.day()))
+            }
+            Schedule::Yearly(..) | Schedule::Weekly(_) => None,
+            Schedule::LastBusinessDay => {
+                let mut date = last_day;
+                while matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+                    date = date - Days::new(1);
+                }
+                Some(date)
+            }
+        }
+    }
+}
+
+// 定期の入出金の規則。終了を省略すると、ずっと続く
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Row {
+    周期: String,
+    開始: NaiveDate,
+    用途: String,
+    金額: Decimal,
+    #[serde(default)]
+    終了: Option<NaiveDate>,
+    #[serde(default)]
+    分類: String,
+    #[serde(default)]
+    通貨: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub account: String,
+    pub schedule: Schedule,
+    pub start: NaiveDate,
+    pub end: Option<NaiveDate>,
+    pub usage: String,
+    pub amount: Decimal,
+    pub category: String,
+    pub currency: String,
+}
+
+impl Rule {
+    // 規則を区別する名前。金額や終了を書き換えても同じ規則として扱う
+    fn key(&self) -> String {
+        format!("{} {} {}", self.schedule, self.start, self.usage)
+    }
+
+    // after より後、until までにこの規則で発生する記録
+    pub fn records(&self, after: Option<NaiveDate>, until: NaiveDate) -> Vec<Record> {
+        let from = match after.and_then(|after| after.succ_opt()) {
+            Some(next) => next.max(self.start),
+            None if after.is_some() => return Vec::new(),
+            None => self.start,
+        };
+        let to = self.end.map_or(until, |end| end.min(until));
+        if to < from {
+            return Vec::new();
+        }
+        self.schedule
+            .occurrences(from, to)
+            .into_iter()
+            .map(|date| {
+                let mut record = Record::new(date, &self.usage, self.amount);
+                record.分類 = self.category.clone();
+                record.通貨 = self.currency.clone();
+                record.タグ = Tags(vec![TAG.to_string()]);
+                record
+            })
+            .collect()
+    }
+}
+
+// dir にある口座ごとの規則ファイルを、口座名の順に全て読む。dir がなければ規則はない
+pub fn load_dir(dir: &Path) -> Result<Vec<Rule>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        paths.push(entry?.path());
+    }
+    paths.sort();
+    let mut rules = Vec::new();
+    for path in paths {
+        if let Some(account) = account_name(&path) {
+            rules.extend(load(&path, &account)?);
+        }
+    }
+    Ok(rules)
+}
+
+fn account_name(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    name.strip_suffix(".csv")
+        .filter(|account| !account.is_empty())
+        .map(str::to_string)
+}
+
+// 1つの口座の規則ファイルを読む
+pub fn load(path: &Path, account: &str) -> Result<Vec<Rule>> {
+    let mut reader = Reader::from_path(path).map_err(|e| Error::from_csv(e, path))?;
+    let header = reader.headers().map_err(|e| Error::from_csv(e, path))?;
+    check_header(
+        header,
+        path,
+        &RECURRING_HEADER[..REQUIRED_COLUMNS],
+        &RECURRING_HEADER,
+    )?;
+    let mut rules = Vec::new();
+    for result in reader.deserialize() {
+        let row: Row = result.map_err(|e| Error::from_csv(e, path))?;
+        let line = rules.len() as u64 + 2;
+        let schedule = row.周期.parse().map_err(|message| Error::Parse {
+            path: path.to_path_buf(),
+            line,
+            column: 2,
+            message,
+        })?;
+        rules.push(Rule {
+            account: account.to_string(),
+            schedule,
+            start: row.開始,
+            end: row.終了,
+            usage: row.用途,
+            amount: row.金額,
+            category: row.分類,
+            currency: if row.通貨.trim().is_empty() {
+                DEFAULT_CURRENCY.to_string()
+            } else {
+                row.通貨.trim().to_uppercase()
+            },
+        });
+    }
+    Ok(rules)
+}
+
+// 口座ごと、規則ごとに、どの日付まで記録したか
+// 記録した日付より後の分だけを作るので、作った記録を消したり書き換えたりしても、作り直さない
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Applied(BTreeMap<String, BTreeMap<String, NaiveDate>>);
+
+impl Applied {
+    // ファイルがなければ、どの規則もまだ記録していないものとして扱う
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| Error::InvalidConfig {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = toml::to_string(self).map_err(|e| Error::InvalidConfig {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_bytes(path, text.as_bytes())
+    }
+
+    fn get(&self, rule: &Rule) -> Option<NaiveDate> {
+        self.0.get(&rule.account)?.get(&rule.key()).copied()
+    }
+
+    // rules を until まで記録したことにする。前に記録した日付より前には戻さない
+    pub fn mark(&mut self, rules: &[Rule], until: NaiveDate) {
+        for rule in rules {
+            let date = self
+                .0
+                .entry(rule.account.clone())
+                .or_default()
+                .entry(rule.key())
+                .or_insert(until);
+            *date = (*date).max(until);
+        }
+    }
+}
+
+// 口座ごとに、前に記録した日付より後、until までに発生する記録をまとめる
+pub fn due(rules: &[Rule], applied: &Applied, until: NaiveDate) -> BTreeMap<String, Vec<Record>> {
+    let mut due: BTreeMap<String, Vec<Record>> = BTreeMap::new();
+    for rule in rules {
+        due.entry(rule.account.clone())
+            .or_default()
+            .extend(rule.records(applied.get(rule), until));
+    }
+    for records in due.values_mut() {
+        records.sort_by_key(|r| r.日付);
+    }
+    due
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn occurrences(schedule: &str, from: &str, to: &str) -> Vec<String> {
+        let schedule: Schedule = schedule.parse().unwrap();
+        schedule
+            .occurrences(date(from), date(to))
+            .iter()
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_occurrences() {
+        assert_eq!(
+            occurrences("monthly:31", "2024-01-15", "2024-04-30"),
+            ["2024-01-31", "2024-02-29", "2024-03-31", "2024-04-30"]
+        );
+        assert_eq!(
+            occurrences("weekly:mon", "2024-01-03", "2024-01-22"),
+            ["2024-01-08", "2024-01-15", "2024-01-22"]
+        );
+        assert_eq!(
+            occurrences("yearly:02-29", "2023-01-01", "2024-12-31"),
+            ["2023-02-28", "2024-02-29"]
+        );
+        // 2024-03-31 と 2024-08-31 は日曜日、2024-11-30 は土曜日
+        assert_eq!(
+            occurrences("last-business-day", "2024-03-01", "2024-03-31"),
+            ["2024-03-29"]
+        );
+        assert_eq!(
+            occurrences("last-business-day", "2024-11-01", "2024-11-30"),
+            ["2024-11-29"]
+        );
+        assert!("monthly:32".parse::<Schedule>().is_err());
+        assert!("daily".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn test_rule_stops_at_end_date() {
+        let rule = Rule {
+            account: "銀行".to_string(),
+            schedule: Schedule::Monthly(27),
+            start: date("2024-01-01"),
+            end: Some(date("2024-02-28")),
+            usage: "家賃".to_string(),
+            amount: (-80000).into(),
+            category: "住居".to_string(),
+            currency: DEFAULT_CURRENCY.to_string(),
+        };
+        let records = rule.records(None, date("2024-12-31"));
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].日付, date("2024-02-27"));
+        assert_eq!(records[1].タグ, Tags(vec![TAG.to_string()]));
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let rules = dir.path().join(RECURRING_DIR);
+        assert!(load_dir(&rules).unwrap().is_empty());
+
+        fs::create_dir(&rules).unwrap();
+        fs::write(
+            rules.join("銀行.csv"),
+            "周期,開始,用途,金額\nmonthly:27,2024-01-01,家賃,-80000\n",
+        )
+        .unwrap();
+        fs::write(
+            rules.join("外貨.csv"),
+            "周期,開始,用途,金額,終了,分類,通貨\nyearly:04-01,2024-01-01,配当,100,,,usd\n",
+        )
+        .unwrap();
+        fs::write(rules.join("メモ.txt"), "").unwrap();
+        let rules = load_dir(&rules).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].account, "外貨");
+        assert_eq!(rules[0].currency, "USD");
+        assert_eq!(rules[1].account, "銀行");
+        assert_eq!(rules[1].schedule, Schedule::Monthly(27));
+    }
+
+    #[test]
+    fn test_applied_skips_recorded_occurrences() {
+        let rule = Rule {
+            account: "銀行".to_string(),
+            schedule: "weekly:mon".parse().unwrap(),
+            start: date("2024-01-01"),
+            end: None,
+            usage: "お小遣い".to_string(),
+            amount: (-1000).into(),
+            category: String::new(),
+            currency: DEFAULT_CURRENCY.to_string(),
+        };
+        let rules = [rule];
+        let mut applied = Applied::default();
+        assert_eq!(due(&rules, &applied, date("2024-01-15"))["銀行"].len(), 3);
+
+        applied.mark(&rules, date("2024-01-15"));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(RECURRING_DIR).join(APPLIED_FILE);
+        applied.save(&path).unwrap();
+        let applied = Applied::load(&path).unwrap();
+        // 記録した日付より後の分だけを作る
+        let records = &due(&rules, &applied, date("2024-01-29"))["銀行"];
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].日付, date("2024-01-22"));
+        assert!(due(&rules, &applied, date("2024-01-15"))["銀行"].is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::ledger::csv::write_bytes;
+use crate::record::{Record, DEFAULT_CURRENCY};
+
+// データディレクトリに置く、口座の情報のファイル
+pub const REGISTRY_FILE: &str = "accounts.toml";
+
+#[derive(Copy, Clone, PartialEq, Debug, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccountType {
+    /// 現金
+    #[default]
+    Cash,
+    /// 銀行口座
+    Bank,
+    /// クレジットカード
+    CreditCard,
+    /// 証券口座
+    Investment,
+    /// ローン
+    Loan,
+}
+
+impl AccountType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AccountType::Cash => "現金",
+            AccountType::Bank => "銀行口座",
+            AccountType::CreditCard => "クレジットカード",
+            AccountType::Investment => "証券口座",
+            AccountType::Loan => "ローン",
+        }
+    }
+
+    // 負債の口座か (仕訳帳では Liabilities: の勘定になる)
+    pub fn is_liability(&self) -> bool {
+        matches!(self, AccountType::CreditCard | AccountType::Loan)
+    }
+}
+
+// 口座の情報。登録していない口座 (この機能より前に作った口座) は既定値として扱う
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountInfo {
+    #[serde(rename = "type", default)]
+    pub kind: AccountType,
+    // 入出金で --currency を省略したときの通貨
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    // 最初の記録より前の残高
+    #[serde(default)]
+    pub opening_balance: Decimal,
+    // 閉じた口座には記録を追加できない
+    #[serde(default)]
+    pub closed: bool,
+}
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
+
+impl Default for AccountInfo {
+    fn default() -> Self {
+        Self {
+            kind: AccountType::default(),
+            currency: default_currency(),
+            opening_balance: Decimal::ZERO,
+            closed: false,
+        }
+    }
+}
+
+impl AccountInfo {
+    // 開始残高を、どの記録よりも前の日付の記録にする
+    pub fn opening_record(&self) -> Option<Record> {
+        if self.opening_balance.is_zero() {
+            return None;
+        }
+        let mut record = Record::new(NaiveDate::MIN, "開始残高", self.opening_balance);
+        record.通貨 = self.currency.clone();
+        Some(record)
+    }
+
+    // 開始残高と、口座の通貨の記録の合計
+    pub fn balance(&self, records: &[Record]) -> Decimal {
+        self.opening_balance
+            + records
+                .iter()
+                .filter(|r| r.通貨 == self.currency)
+                .map(|r| r.金額)
+                .sum::<Decimal>()
+    }
+}
+
+// 口座名ごとの口座の情報。変更するたびにファイルへ書き出す
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Registry {
+    // None ならファイルに書き出さない
+    path: Option<PathBuf>,
+    accounts: BTreeMap<String, AccountInfo>,
+}
+
+impl Registry {
+    // ファイルがなければ、どの口座も登録していないものとして扱う
+    pub fn load(path: &Path) -> Result<Self> {
+        let accounts = if path.exists() {
+            let text = std::fs::read_to_string(path)?;
+            toml::from_str(&text).map_err(|e| Error::InvalidConfig {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            accounts,
+        })
+    }
+
+    pub fn get(&self, account: &str) -> AccountInfo {
+        self.accounts.get(account).cloned().unwrap_or_default()
+    }
+
+    pub fn insert(&mut self, account: &str, info: AccountInfo) -> Result<()> {
+        self.accounts.insert(account.to_string(), info);
+        self.save()
+    }
+
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+        if let Some(info) = self.accounts.remove(from) {
+            self.accounts.insert(to.to_string(), info);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text = toml::to_string(&self.accounts).map_err(|e| Error::InvalidConfig {
+            path: path.clone(),
+            message: e.to_string(),
+        })?;
+        // 書き込み途中で止まっても、口座の一覧が壊れないようにする
+        write_bytes(path, text.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(REGISTRY_FILE);
+        let mut registry = Registry::load(&path).unwrap();
+        assert_eq!(registry.get("財布"), AccountInfo::default());
+
+        let card = AccountInfo {
+            kind: AccountType::CreditCard,
+            currency: "USD".to_string(),
+            opening_balance: Decimal::new(-12050, 2),
+            closed: false,
+        };
+        registry.insert("カード", card.clone()).unwrap();
+        registry.rename("カード", "旅行用カード").unwrap();
+
+        let registry = Registry::load(&path).unwrap();
+        assert_eq!(registry.get("旅行用カード"), card);
+        assert_eq!(registry.get("カード"), AccountInfo::default());
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("type = \"credit-card\""), "{}", text);
+    }
+}
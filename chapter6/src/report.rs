@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+
+use crate::record::Record;
+
+#[derive(Copy, Clone, PartialEq, Debug, ValueEnum)]
+pub enum GroupBy {
+    /// 月ごと (2024-01)
+    Month,
+    /// ISO 週ごと (2024-W01)
+    Week,
+    /// 年ごと (2024)
+    Year,
+    /// 用途ごと
+    Usage,
+    /// 分類ごと
+    Category,
+}
+
+impl GroupBy {
+    fn key(&self, record: &Record) -> String {
+        match self {
+            GroupBy::Month => record.日付.format("%Y-%m").to_string(),
+            GroupBy::Week => record.日付.format("%G-W%V").to_string(),
+            GroupBy::Year => record.日付.format("%Y").to_string(),
+            GroupBy::Usage => record.用途.clone(),
+            GroupBy::Category => record.分類.clone(),
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            GroupBy::Usage => "用途",
+            GroupBy::Category => "分類",
+            _ => "期間",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, ValueEnum)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+    Markdown,
+}
+
+// レポートの1行
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub key: String,
+    pub income: Decimal,
+    pub expense: Decimal,
+    // 行までの累計残高
+    pub balance: Decimal,
+}
+
+impl Row {
+    pub fn net(&self) -> Decimal {
+        self.income - self.expense
+    }
+}
+
+pub struct Report {
+    pub group_by: GroupBy,
+    pub rows: Vec<Row>,
+}
+
+impl Report {
+    // from..=to の記録をグループごとに集計する
+    // 残高は from より前の記録も含めた累計にする
+    // 記録は全て同じ通貨に換算しておくこと
+    pub fn new(
+        records: &[Record],
+        group_by: GroupBy,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Self {
+        let mut opening = Decimal::ZERO;
+        let mut groups: BTreeMap<String, (Decimal, Decimal)> = BTreeMap::new();
+        for record in records {
+            if from.is_some_and(|from| record.日付 < from) {
+                opening += record.金額;
+                continue;
+            }
+            if to.is_some_and(|to| record.日付 > to) {
+                continue;
+            }
+            let (income, expense) = groups.entry(group_by.key(record)).or_default();
+            if record.金額 >= Decimal::ZERO {
+                *income += record.金額;
+            } else {
+                *expense -= record.金額;
+            }
+        }
+
+        let mut balance = opening;
+        let rows = groups
+            .into_iter()
+            .map(|(key, (income, expense))| {
+                balance += income - expense;
+                Row {
+                    key,
+                    income,
+                    expense,
+                    balance,
+                }
+            })
+            .collect();
+        Self { group_by, rows }
+    }
+
+    fn total(&self) -> Row {
+        Row {
+            key: "合計".to_string(),
+            income: self.rows.iter().map(|r| r.income).sum(),
+            expense: self.rows.iter().map(|r| r.expense).sum(),
+            balance: self.rows.last().map_or(Decimal::ZERO, |r| r.balance),
+        }
+    }
+
+    fn header(&self) -> [&'static str; 5] {
+        [self.group_by.label(), "収入", "支出", "収支", "残高"]
+    }
+
+    fn cells(row: &Row) -> [String; 5] {
+        [
+            row.key.clone(),
+            row.income.to_string(),
+            row.expense.to_string(),
+            row.net().to_string(),
+            row.balance.to_string(),
+        ]
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Table => self.render_table(),
+            Format::Csv => self.render_csv(),
+            Format::Json => self.render_json(),
+            Format::Markdown => self.render_markdown(),
+        }
+    }
+
+    fn render_table(&self) -> String {
+        let rows: Vec<Vec<String>> = self.rows.iter().map(|r| Self::cells(r).to_vec()).collect();
+        let total = Self::cells(&self.total()).to_vec();
+        format_table(&self.header(), &rows, Some(&total))
+    }
+
+    fn render_csv(&self) -> String {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.write_record(self.header()).unwrap();
+        for row in &self.rows {
+            writer.write_record(Self::cells(row)).unwrap();
+        }
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    fn render_json(&self) -> String {
+        let header = self.header();
+        let rows: Vec<serde_json::Value> = self
+            .rows
+            .iter()
+            .map(|row| {
+                serde_json::json!({
+                    header[0]: row.key,
+                    header[1]: row.income,
+                    header[2]: row.expense,
+                    header[3]: row.net(),
+                    header[4]: row.balance,
+                })
+            })
+            .collect();
+        let total = self.total();
+        let report = serde_json::json!({
+            "rows": rows,
+            "total": {
+                header[1]: total.income,
+                header[2]: total.expense,
+                header[3]: total.net(),
+                header[4]: total.balance,
+            },
+        });
+        serde_json::to_string_pretty(&report).unwrap() + "\n"
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut out = format!("| {} |\n", self.header().join(" | "));
+        out.push_str("| --- | ---: | ---: | ---: | ---: |\n");
+        for row in self.rows.iter().chain([&self.total()]) {
+            out.push_str(&format!("| {} |\n", Self::cells(row).join(" | ")));
+        }
+        out
+    }
+}
+
+// 端末向けの表を作る。1列目は左寄せ、それ以外は右寄せにする
+// footer があれば区切り線の後に出力する
+pub fn format_table(header: &[&str], rows: &[Vec<String>], footer: Option<&[String]>) -> String {
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    let mut lines: Vec<&[String]> = vec![&header];
+    lines.extend(rows.iter().map(|r| r.as_slice()));
+    lines.extend(footer);
+
+    // 全角文字は幅2として列幅を揃える
+    let mut widths = vec![0; header.len()];
+    for line in &lines {
+        for (width, cell) in widths.iter_mut().zip(line.iter()) {
+            *width = (*width).max(display_width(cell));
+        }
+    }
+    let mut out = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if footer.is_some() && i == lines.len() - 1 {
+            let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+            out.push_str(&rule.join("  "));
+            out.push('\n');
+        }
+        let cells: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(j, (cell, width))| {
+                let pad = " ".repeat(width - display_width(cell));
+                if j == 0 {
+                    format!("{}{}", cell, pad)
+                } else {
+                    format!("{}{}", pad, cell)
+                }
+            })
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn display_width(s: &str) -> usize {
+    s.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(date: &str, usage: &str, amount: i32) -> Record {
+        Record::new(date.parse().unwrap(), usage, amount)
+    }
+
+    fn records() -> Vec<Record> {
+        vec![
+            record("2024-02-10", "食費", -3000),
+            record("2024-01-25", "給料", 200000),
+            record("2024-01-05", "食費", -5000),
+            record("2024-03-25", "給料", 200000),
+        ]
+    }
+
+    #[test]
+    fn test_group_by_month() {
+        let report = Report::new(&records(), GroupBy::Month, None, None);
+        let keys: Vec<&str> = report.rows.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, ["2024-01", "2024-02", "2024-03"]);
+        assert_eq!(
+            report.rows[0],
+            Row {
+                key: "2024-01".to_string(),
+                income: Decimal::from(200000),
+                expense: Decimal::from(5000),
+                balance: Decimal::from(195000),
+            }
+        );
+        assert_eq!(report.rows[2].balance, Decimal::from(392000));
+    }
+
+    #[test]
+    fn test_date_filter_keeps_opening_balance() {
+        let from = "2024-02-01".parse().ok();
+        let to = "2024-02-29".parse().ok();
+        let report = Report::new(&records(), GroupBy::Usage, from, to);
+        assert_eq!(report.rows.len(), 1);
+        assert_eq!(report.rows[0].key, "食費");
+        assert_eq!(report.rows[0].balance, Decimal::from(192000));
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let report = Report::new(&records(), GroupBy::Year, None, None);
+        assert_eq!(
+            report.render(Format::Markdown),
+            "| 期間 | 収入 | 支出 | 収支 | 残高 |\n\
+             | --- | ---: | ---: | ---: | ---: |\n\
+             | 2024 | 400000 | 8000 | 392000 | 392000 |\n\
+             | 合計 | 400000 | 8000 | 392000 | 392000 |\n"
+        );
+    }
+}
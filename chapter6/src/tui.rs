@@ -0,0 +1,440 @@
+use chrono::{Local, NaiveDate};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Clear, List, ListState, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use rust_decimal::Decimal;
+
+use crate::error::Result;
+use crate::import::profile::parse_amount;
+use crate::ledger::Ledger;
+use crate::record::Record;
+use crate::report::{GroupBy, Report};
+
+// 月ごとの集計に表示する月数
+const SUMMARY_MONTHS: usize = 12;
+const HELP: &str = "↑↓: 選択  Tab: 口座/記録の切り替え  a: 記録を追加  q: 終了";
+const FORM_HELP: &str = "Tab/↑↓: 項目の移動  ←→: 入金/出金  Enter: 保存  Esc: 取り消し";
+
+// 端末の画面で口座と記録を見たり、記録を追加したりする
+pub fn run(ledger: &mut Ledger) -> Result<()> {
+    let mut app = App::new(ledger)?;
+    let mut terminal = ratatui::try_init()?;
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Focus {
+    Accounts,
+    Records,
+}
+
+struct App<'a> {
+    ledger: &'a mut Ledger,
+    accounts: Vec<String>,
+    account_state: ListState,
+    // 選んでいる口座の記録。日付順
+    records: Vec<Record>,
+    record_state: TableState,
+    focus: Focus,
+    form: Option<Form>,
+    // 画面の一番下に出すメッセージ
+    message: String,
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(ledger: &'a mut Ledger) -> Result<Self> {
+        let mut app = Self {
+            ledger,
+            accounts: Vec::new(),
+            account_state: ListState::default(),
+            records: Vec::new(),
+            record_state: TableState::default(),
+            focus: Focus::Accounts,
+            form: None,
+            message: String::new(),
+            quit: false,
+        };
+        app.accounts = app.ledger.accounts()?;
+        if !app.accounts.is_empty() {
+            app.account_state.select(Some(0));
+        }
+        app.load_records()?;
+        Ok(app)
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle_key(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn account(&self) -> Option<&str> {
+        self.account_state
+            .selected()
+            .and_then(|i| self.accounts.get(i))
+            .map(String::as_str)
+    }
+
+    // 選んでいる口座の記録を読み直して、最後の記録を選ぶ
+    fn load_records(&mut self) -> Result<()> {
+        self.records = match self.account() {
+            Some(account) => self.ledger.records(account)?,
+            None => Vec::new(),
+        };
+        self.records.sort_by_key(|r| r.日付);
+        self.record_state.select(self.records.len().checked_sub(1));
+        Ok(())
+    }
+
+    // 読み込みや保存のエラーで画面を閉じないように、エラーはメッセージに出す
+    fn handle_key(&mut self, key: KeyEvent) {
+        if let Err(e) = self.try_handle_key(key) {
+            self.message = format!("エラー：{}", e);
+        }
+    }
+
+    fn try_handle_key(&mut self, key: KeyEvent) -> Result<()> {
+        if let Some(form) = &mut self.form {
+            match form.handle_key(key) {
+                FormAction::None => {}
+                FormAction::Cancel => self.form = None,
+                FormAction::Submit => match form.to_record() {
+                    Ok(mut record) => {
+                        let Some(account) = self.account().map(str::to_string) else {
+                            return Ok(());
+                        };
+                        record.通貨 = self.ledger.account_info(&account).currency;
+                        self.message = format!(
+                            "{} に {} {} {} を追加しました",
+                            account, record.日付, record.用途, record.金額
+                        );
+                        self.ledger.add(&account, record)?;
+                        self.form = None;
+                        self.load_records()?;
+                    }
+                    Err(message) => form.error = message,
+                },
+            }
+            return Ok(());
+        }
+
+        self.message.clear();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Left | KeyCode::Right => {
+                self.focus = match self.focus {
+                    Focus::Accounts => Focus::Records,
+                    Focus::Records => Focus::Accounts,
+                };
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.select(-1)?,
+            KeyCode::Down | KeyCode::Char('j') => self.select(1)?,
+            KeyCode::Char('a') => {
+                if self.account().is_some() {
+                    self.form = Some(Form::new(Local::now().date_naive()));
+                } else {
+                    self.message =
+                        "口座がありません。monestie new で口座を作ってください".to_string();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn select(&mut self, delta: isize) -> Result<()> {
+        let (state_selected, len) = match self.focus {
+            Focus::Accounts => (self.account_state.selected(), self.accounts.len()),
+            Focus::Records => (self.record_state.selected(), self.records.len()),
+        };
+        let Some(selected) = state_selected else {
+            return Ok(());
+        };
+        let selected = selected
+            .saturating_add_signed(delta)
+            .min(len.saturating_sub(1));
+        match self.focus {
+            Focus::Accounts => {
+                self.account_state.select(Some(selected));
+                self.load_records()?;
+            }
+            Focus::Records => self.record_state.select(Some(selected)),
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Length(36), Constraint::Fill(1)]).areas(main);
+        let [accounts, summary] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(SUMMARY_MONTHS as u16 + 3),
+        ])
+        .areas(left);
+
+        self.draw_accounts(frame, accounts);
+        self.draw_summary(frame, summary);
+        self.draw_records(frame, right);
+        let status_text = if self.message.is_empty() {
+            HELP
+        } else {
+            &self.message
+        };
+        frame.render_widget(Paragraph::new(status_text), status);
+        if let Some(form) = &self.form {
+            form.draw(frame, self.account().unwrap_or_default());
+        }
+    }
+
+    fn block(&self, title: &str, focus: Option<Focus>) -> Block<'static> {
+        let block = Block::bordered().title(title.to_string());
+        if focus == Some(self.focus) {
+            block.border_style(Style::default().fg(Color::Cyan))
+        } else {
+            block
+        }
+    }
+
+    fn draw_accounts(&mut self, frame: &mut Frame, area: Rect) {
+        let list = List::new(self.accounts.clone())
+            .block(self.block("口座", Some(Focus::Accounts)))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.account_state);
+    }
+
+    // 選んでいる口座の月ごとの収支。通貨は換算しない
+    fn draw_summary(&self, frame: &mut Frame, area: Rect) {
+        let report = Report::new(&self.records, GroupBy::Month, None, None);
+        let skip = report.rows.len().saturating_sub(SUMMARY_MONTHS);
+        let rows = report.rows[skip..].iter().map(|row| {
+            Row::new([
+                row.key.clone(),
+                row.income.to_string(),
+                row.expense.to_string(),
+                row.balance.to_string(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(7),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(
+            Row::new(["月", "収入", "支出", "残高"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(self.block("月ごとの収支", None));
+        frame.render_widget(table, area);
+    }
+
+    fn draw_records(&mut self, frame: &mut Frame, area: Rect) {
+        let rows = self.records.iter().map(|r| {
+            let style = if r.金額 < Decimal::ZERO {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            };
+            Row::new([
+                r.日付.to_string(),
+                r.用途.clone(),
+                r.金額.to_string(),
+                r.通貨.clone(),
+                r.分類.clone(),
+                r.タグ.to_string(),
+            ])
+            .style(style)
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(10),
+                Constraint::Fill(2),
+                Constraint::Length(12),
+                Constraint::Length(4),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(
+            Row::new(["日付", "用途", "金額", "通貨", "分類", "タグ"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(self.block("記録", Some(Focus::Records)))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, &mut self.record_state);
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum FormAction {
+    None,
+    Cancel,
+    Submit,
+}
+
+const FIELDS: [&str; 4] = ["日付", "用途", "金額", "分類"];
+
+// 記録を追加する入力欄
+#[derive(Debug, Clone, PartialEq)]
+struct Form {
+    deposit: bool,
+    // FIELDS の順の入力
+    values: [String; 4],
+    // 0 は入金/出金の切り替え、1 以降は values[focus - 1]
+    focus: usize,
+    error: String,
+}
+
+impl Form {
+    fn new(today: NaiveDate) -> Self {
+        Self {
+            deposit: false,
+            values: [
+                today.to_string(),
+                String::new(),
+                String::new(),
+                String::new(),
+            ],
+            focus: 0,
+            error: String::new(),
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> FormAction {
+        let count = FIELDS.len() + 1;
+        match key.code {
+            KeyCode::Esc => return FormAction::Cancel,
+            KeyCode::Enter => return FormAction::Submit,
+            KeyCode::Tab | KeyCode::Down => self.focus = (self.focus + 1) % count,
+            KeyCode::BackTab | KeyCode::Up => self.focus = (self.focus + count - 1) % count,
+            KeyCode::Left | KeyCode::Right | KeyCode::Char(' ') if self.focus == 0 => {
+                self.deposit = !self.deposit;
+            }
+            KeyCode::Char(c) if self.focus > 0 => self.values[self.focus - 1].push(c),
+            KeyCode::Backspace if self.focus > 0 => {
+                self.values[self.focus - 1].pop();
+            }
+            _ => {}
+        }
+        FormAction::None
+    }
+
+    // 入力を確かめて記録にする。不正な入力はエラーメッセージを返す
+    fn to_record(&self) -> std::result::Result<Record, String> {
+        let [date, usage, amount, category] = &self.values;
+        let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+            .map_err(|_| format!("日付 {} は 2024-01-31 の形で入力してください", date))?;
+        if usage.trim().is_empty() {
+            return Err("用途を入力してください".to_string());
+        }
+        let amount = parse_amount(amount)?;
+        if amount <= Decimal::ZERO {
+            return Err("金額は正の数で入力してください".to_string());
+        }
+        let amount = if self.deposit { amount } else { -amount };
+        let mut record = Record::new(date, usage.trim(), amount);
+        record.分類 = category.trim().to_string();
+        Ok(record)
+    }
+
+    fn draw(&self, frame: &mut Frame, account: &str) {
+        let [area] = Layout::horizontal([Constraint::Length(60)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::vertical([Constraint::Length(10)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let selected = |i: usize| {
+            if self.focus == i {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            }
+        };
+        let kind = if self.deposit { "入金" } else { "出金" };
+        let mut lines = vec![Line::styled(format!("種類: ◀ {} ▶", kind), selected(0))];
+        for (i, (name, value)) in FIELDS.iter().zip(&self.values).enumerate() {
+            lines.push(Line::styled(
+                format!("{}: {}", name, value),
+                selected(i + 1),
+            ));
+        }
+        lines.push(Line::styled(
+            self.error.clone(),
+            Style::default().fg(Color::Red),
+        ));
+        lines.push(Line::raw(FORM_HELP));
+
+        let block = Block::bordered().title(format!("{} に記録を追加", account));
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::csv::CsvStorage;
+    use crate::registry::AccountInfo;
+
+    fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\t' => KeyCode::Tab,
+                '\n' => KeyCode::Enter,
+                '\x08' => KeyCode::Backspace,
+                c => KeyCode::Char(c),
+            };
+            app.handle_key(KeyEvent::from(code));
+        }
+    }
+
+    #[test]
+    fn test_form_adds_record_after_validation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ledger = Ledger::new(Box::new(CsvStorage::new(dir.path())));
+        ledger
+            .create_account("財布", AccountInfo::default())
+            .unwrap();
+        let mut app = App::new(&mut ledger).unwrap();
+
+        press(&mut app, "a\t");
+        // 日付を消して、不正な日付を入れる
+        press(&mut app, &"\x08".repeat(10));
+        press(&mut app, "2024-02-30\t昼ご飯\t1,200\t食費\n");
+        let form = app.form.as_ref().unwrap();
+        assert!(form.error.contains("2024-02-30"), "{}", form.error);
+
+        press(&mut app, "\t\t\x08\x0829\n");
+        assert_eq!(app.form, None);
+        let records = ledger.records("財布").unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            (
+                records[0].日付.to_string(),
+                records[0].金額,
+                records[0].分類.as_str()
+            ),
+            ("2024-02-29".to_string(), Decimal::from(-1200), "食費")
+        );
+    }
+}
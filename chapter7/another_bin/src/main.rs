@@ -1,10 +1,352 @@
-use my_library::library::{book::Book, bookshelf::Bookshelf};
+mod table;
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use my_library::library::{
+    book::Book,
+    bookshelf::Bookshelf,
+    error::Error,
+    export::{bibtex, csl_json},
+    lending,
+};
+use serde_json::json;
+
+use table::format_table;
+
+#[derive(Parser)]
+#[clap(version = "1.0", about = "本棚を管理する")]
+struct App {
+    #[clap(subcommand)]
+    command: Command,
+    /// 本棚のファイル。拡張子が .db か .sqlite なら SQLite、それ以外は JSON
+    #[clap(long, default_value = "bookshelf.json", global = true)]
+    file: PathBuf,
+    /// 一覧や検索結果の出力形式
+    #[clap(long, value_enum, default_value_t = Output::Table, global = true)]
+    output: Output,
+}
+
+#[derive(Copy, Clone, PartialEq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 本を追加する
+    Add(AddArgs),
+    /// 本を取り除く
+    Remove(RemoveArgs),
+    /// 本を検索する (例: author:山田 rust)
+    Search(SearchArgs),
+    /// すべての本を表示する
+    List,
+    /// CSV から本を追加する
+    Import(ImportArgs),
+    /// 本を BibTeX・CSL-JSON・JSON で書き出す
+    Export(ExportArgs),
+}
+
+#[derive(Args)]
+struct AddArgs {
+    title: String,
+    /// 著者。共著なら複数回指定する
+    #[clap(long = "author", required = true)]
+    authors: Vec<String>,
+    #[clap(long)]
+    isbn: Option<String>,
+    #[clap(long)]
+    publisher: Option<String>,
+    /// 出版年
+    #[clap(long)]
+    year: Option<u16>,
+    /// タグ。複数回指定できる
+    #[clap(long = "tag")]
+    tags: Vec<String>,
+    /// タイトルの読み (かな)
+    #[clap(long)]
+    reading: Option<String>,
+}
+
+impl AddArgs {
+    fn run(&self, shelf: &mut Bookshelf) -> Result<(), Error> {
+        let mut book = Book::new(&self.title, &self.authors[0]);
+        for author in &self.authors[1..] {
+            book = book.author(author);
+        }
+        for tag in &self.tags {
+            book = book.tag(tag);
+        }
+        if let Some(isbn) = &self.isbn {
+            book = book.isbn(isbn.parse()?);
+        }
+        if let Some(publisher) = &self.publisher {
+            book = book.publisher(publisher);
+        }
+        if let Some(year) = self.year {
+            book = book.year(year);
+        }
+        if let Some(reading) = &self.reading {
+            book = book.reading(reading);
+        }
+        let id = shelf.add_book(book)?;
+        println!("{}", id);
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct RemoveArgs {
+    /// 本の ID (先頭の数文字でもよい) か ISBN
+    book: String,
+}
+
+impl RemoveArgs {
+    // 貸出の記録があれば、貸出中の本は取り除かない
+    fn run(&self, shelf: &mut Bookshelf, file: &Path) -> Result<(), Error> {
+        let id = shelf.resolve(&self.book)?;
+        let book = lending::remove_book(file, shelf, id)?;
+        println!("{} を取り除きました", book.title);
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct SearchArgs {
+    #[clap(required = true)]
+    query: Vec<String>,
+    /// 表示する件数
+    #[clap(long)]
+    limit: Option<usize>,
+}
+
+impl SearchArgs {
+    fn run(&self, shelf: &Bookshelf, output: Output) {
+        let mut found = shelf.search(&self.query.join(" "));
+        if let Some(limit) = self.limit {
+            found.truncate(limit);
+        }
+        match output {
+            Output::Table => {
+                let rows: Vec<Vec<String>> = found
+                    .iter()
+                    .map(|(score, book)| {
+                        let mut row = vec![score.to_string()];
+                        row.extend(book_row(book));
+                        row
+                    })
+                    .collect();
+                let mut header = vec!["スコア"];
+                header.extend(BOOK_HEADER);
+                print!("{}", format_table(&header, &rows));
+            }
+            Output::Json => {
+                let found: Vec<_> = found
+                    .iter()
+                    .map(|(score, book)| json!({ "score": score, "book": book }))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&found).unwrap());
+            }
+        }
+    }
+}
+
+#[derive(Args)]
+struct ImportArgs {
+    /// title,authors,isbn,publisher,year,tags,reading の列を持つ CSV (著者とタグは ; で区切る)
+    src_file_name: PathBuf,
+}
+
+impl ImportArgs {
+    fn run(&self, shelf: &mut Bookshelf) -> Result<(), Error> {
+        let count = shelf.import_csv(File::open(&self.src_file_name)?)?;
+        println!("{} 冊を追加しました", count);
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, ValueEnum)]
+enum ExportFormat {
+    Bibtex,
+    CslJson,
+    Json,
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    #[clap(long, value_enum, default_value_t = ExportFormat::Bibtex)]
+    format: ExportFormat,
+    /// 出力先。省略時は標準出力
+    #[clap(long, short)]
+    out: Option<PathBuf>,
+}
+
+impl ExportArgs {
+    fn run(&self, shelf: &Bookshelf) -> Result<(), Error> {
+        let text = match self.format {
+            ExportFormat::Bibtex => bibtex(shelf.books()),
+            ExportFormat::CslJson => {
+                serde_json::to_string_pretty(&csl_json(shelf.books())).unwrap() + "\n"
+            }
+            ExportFormat::Json => {
+                let books: Vec<&Book> = shelf.books().collect();
+                serde_json::to_string_pretty(&books).unwrap() + "\n"
+            }
+        };
+        match &self.out {
+            Some(path) => std::fs::write(path, text)?,
+            None => print!("{}", text),
+        }
+        Ok(())
+    }
+}
+
+const BOOK_HEADER: [&str; 6] = ["ID", "タイトル", "著者", "ISBN", "出版年", "タグ"];
+
+// 表の1行。ID は先頭8文字だけ表示する (remove にはそのまま渡せる)
+fn book_row(book: &Book) -> Vec<String> {
+    vec![
+        book.id.to_string()[..8].to_string(),
+        book.title.clone(),
+        book.authors.join(", "),
+        book.isbn
+            .as_ref()
+            .map(|i| i.to_string())
+            .unwrap_or_default(),
+        book.year.map(|y| y.to_string()).unwrap_or_default(),
+        book.tags.join(", "),
+    ]
+}
+
+fn list(shelf: &Bookshelf, output: Output) {
+    match output {
+        Output::Table => {
+            let rows: Vec<Vec<String>> = shelf.books().map(book_row).collect();
+            print!("{}", format_table(&BOOK_HEADER, &rows));
+        }
+        Output::Json => {
+            let books: Vec<&Book> = shelf.books().collect();
+            println!("{}", serde_json::to_string_pretty(&books).unwrap());
+        }
+    }
+}
+
 fn main() {
-    let mut shelf = Bookshelf::new();
-    let book1 = Book::new("すごいぞChatGPT!AIを使って学ぼうRust!", "山田太郎");
-    let book2 = Book::new("Pythonプログラミング入門", "山田花子");
-    shelf.add_book(book1);
-    shelf.add_book(book2);
-    let found_books = shelf.search_books("chatgpt");
-    println!("{:?}", found_books);
+    let args = App::parse();
+    if let Err(e) = run(args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: App) -> Result<(), Error> {
+    let mut shelf = Bookshelf::load(&args.file)?;
+    // 本棚を変えるコマンドのときだけ保存する
+    match args.command {
+        Command::Add(add) => add.run(&mut shelf)?,
+        Command::Remove(remove) => remove.run(&mut shelf, &args.file)?,
+        Command::Import(import) => import.run(&mut shelf)?,
+        Command::Search(search) => {
+            search.run(&shelf, args.output);
+            return Ok(());
+        }
+        Command::List => {
+            list(&shelf, args.output);
+            return Ok(());
+        }
+        Command::Export(export) => return export.run(&shelf),
+    }
+    shelf.save(&args.file)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use my_library::library::lending::Lending;
+
+    use super::*;
+
+    fn run_in(file: &Path, args: &[&str]) -> Result<(), Error> {
+        let file = file.to_str().unwrap();
+        let mut argv = vec!["another_bin", "--file", file];
+        argv.extend(args);
+        run(App::parse_from(argv))
+    }
+
+    #[test]
+    fn test_add_remove_and_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("bookshelf.json");
+        run_in(
+            &file,
+            &["add", "プログラミングRust", "--author", "Jim Blandy"],
+        )
+        .unwrap();
+        let csv = dir.path().join("books.csv");
+        std::fs::write(
+            &csv,
+            "title,authors,isbn\nPythonプログラミング入門,山田花子,978-4-87311-855-0\n",
+        )
+        .unwrap();
+        run_in(&file, &["import", csv.to_str().unwrap()]).unwrap();
+        assert_eq!(Bookshelf::load(&file).unwrap().len(), 2);
+
+        run_in(&file, &["remove", "9784873118550"]).unwrap();
+        let shelf = Bookshelf::load(&file).unwrap();
+        assert_eq!(shelf.len(), 1);
+        assert_eq!(shelf.books().next().unwrap().title, "プログラミングRust");
+        assert!(matches!(
+            run_in(&file, &["remove", "9784873118550"]),
+            Err(Error::NoMatchingBook(_))
+        ));
+    }
+
+    #[test]
+    fn test_remove_book_on_loan() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("bookshelf.json");
+        let mut lending = Lending::new(Bookshelf::new());
+        let book = lending
+            .add_book(Book::new("プログラミングRust", "Jim Blandy"))
+            .unwrap();
+        let member = lending.add_member("山田太郎").unwrap();
+        lending
+            .checkout(book, member, NaiveDate::from_ymd_opt(2024, 4, 1).unwrap())
+            .unwrap();
+        lending.save(&file).unwrap();
+
+        let id = book.to_string();
+        assert!(matches!(
+            run_in(&file, &["remove", &id]),
+            Err(Error::BookOnLoan(_))
+        ));
+        assert_eq!(Lending::load(&file).unwrap().shelf().len(), 1);
+    }
+
+    #[test]
+    fn test_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("bookshelf.json");
+        run_in(
+            &file,
+            &["add", "プログラミングRust", "--author", "Jim Blandy"],
+        )
+        .unwrap();
+        let out = dir.path().join("books.json");
+        let out_arg = out.to_str().unwrap();
+        run_in(&file, &["export", "--format", "json", "--out", out_arg]).unwrap();
+        let books: Vec<Book> =
+            serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].title, "プログラミングRust");
+        assert_eq!(
+            books[0].id,
+            Bookshelf::load(&file).unwrap().books().next().unwrap().id
+        );
+    }
 }
@@ -0,0 +1,45 @@
+// 端末向けの表を作る。全角文字は幅2として列幅を揃える
+pub fn format_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    let mut lines: Vec<&[String]> = vec![&header];
+    lines.extend(rows.iter().map(|r| r.as_slice()));
+
+    let mut widths = vec![0; header.len()];
+    for line in &lines {
+        for (width, cell) in widths.iter_mut().zip(line.iter()) {
+            *width = (*width).max(display_width(cell));
+        }
+    }
+    let mut out = String::new();
+    for line in lines {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - display_width(cell))))
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn display_width(s: &str) -> usize {
+    s.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_table() {
+        let rows = vec![
+            vec!["Rust入門".to_string(), "山田".to_string()],
+            vec!["Python".to_string(), "".to_string()],
+        ];
+        assert_eq!(
+            format_table(&["タイトル", "著者"], &rows),
+            "タイトル  著者\nRust入門  山田\nPython\n"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use std::{path::PathBuf, sync::RwLock};
+
+use actix_web::{
+    delete, error, get,
+    http::{header, StatusCode},
+    post, put, web, HttpResponse, ResponseError,
+};
+use my_library::library::{book::Book, bookshelf::Bookshelf, error::Error, lending};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+// API の説明 (OpenAPI 3.0)。/openapi.json で返す
+pub const OPENAPI: &str = include_str!("../openapi.json");
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+pub struct AppState {
+    shelf: RwLock<Bookshelf>,
+    // Some なら、本棚を変えるたびにファイルへ保存する
+    file: Option<PathBuf>,
+}
+
+impl AppState {
+    pub fn new(shelf: Bookshelf) -> Self {
+        Self {
+            shelf: RwLock::new(shelf),
+            file: None,
+        }
+    }
+
+    // ファイルから本棚を読み、変更したら同じファイルに保存する
+    pub fn open(file: PathBuf) -> Result<Self, Error> {
+        Ok(Self {
+            shelf: RwLock::new(Bookshelf::load(&file)?),
+            file: Some(file),
+        })
+    }
+
+    // メモリ上の本棚を変えてから保存し、保存に失敗したら変更を取り消す
+    // 本棚は写さないが、保存では本棚全体を書き直す。本の数に比例した時間がかかり、
+    // その間はほかの読み書きを待たせるので、大きな本棚では SQLite のファイルを使うか、変更をまとめて行う
+    fn modify<T>(
+        &self,
+        f: impl FnOnce(&mut Bookshelf) -> Result<(T, Undo), Error>,
+    ) -> Result<T, ApiError> {
+        let mut shelf = self.shelf.write().unwrap();
+        let (value, undo) = f(&mut shelf)?;
+        if let Some(file) = &self.file {
+            if let Err(e) = shelf.save(file) {
+                undo.apply(&mut shelf)?;
+                return Err(e.into());
+            }
+        }
+        Ok(value)
+    }
+}
+
+// 保存に失敗したときに、変更を取り消す方法
+enum Undo {
+    // 追加した本を取り除く
+    Remove(Uuid),
+    // 置き換える前の本に戻す
+    Replace(Book),
+    // 取り除いた本を戻す (本棚の最後に戻る)
+    Restore(Book),
+}
+
+impl Undo {
+    fn apply(self, shelf: &mut Bookshelf) -> Result<(), Error> {
+        match self {
+            Undo::Remove(id) => shelf.remove_book(id).map(|_| ()),
+            Undo::Replace(book) => shelf.replace_book(book.id, book).map(|_| ()),
+            Undo::Restore(book) => shelf.add_book(book).map(|_| ()),
+        }
+    }
+}
+
+// my_library のエラーを、HTTP のステータスと {"error": "..."} の JSON にする
+#[derive(Debug)]
+pub struct ApiError(Error);
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        ApiError(e)
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self.0 {
+            Error::InvalidIsbn { .. } | Error::InvalidBook(_) | Error::AmbiguousId(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            Error::DuplicateIsbn(_) | Error::DuplicateId(_) | Error::BookOnLoan(_) => {
+                StatusCode::CONFLICT
+            }
+            Error::BookNotFound(_) | Error::NoMatchingBook(_) => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(json!({ "error": self.0.to_string() }))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Page {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl Page {
+    fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    fn slice<T: Clone>(&self, items: &[T]) -> Vec<T> {
+        items
+            .iter()
+            .skip(self.offset)
+            .take(self.limit())
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Paged<T> {
+    total: usize,
+    offset: usize,
+    limit: usize,
+    items: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    q: String,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SearchResult<'a> {
+    score: i64,
+    book: &'a Book,
+}
+
+#[get("/books")]
+async fn list_books(state: web::Data<AppState>, page: web::Query<Page>) -> HttpResponse {
+    let shelf = state.shelf.read().unwrap();
+    let books: Vec<&Book> = shelf.books().collect();
+    HttpResponse::Ok().json(Paged {
+        total: books.len(),
+        offset: page.offset,
+        limit: page.limit(),
+        items: page.slice(&books),
+    })
+}
+
+// 本文の id は省略できる。省略すれば新しい ID を付ける
+#[post("/books")]
+async fn create_book(
+    state: web::Data<AppState>,
+    book: web::Json<Book>,
+) -> Result<HttpResponse, ApiError> {
+    let book = state.modify(|shelf| {
+        let id = shelf.add_book(book.into_inner())?;
+        let book = shelf.get(id).cloned().ok_or(Error::BookNotFound(id))?;
+        Ok((book, Undo::Remove(id)))
+    })?;
+    Ok(HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/books/{}", book.id)))
+        .json(book))
+}
+
+#[get("/books/{id}")]
+async fn get_book(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let shelf = state.shelf.read().unwrap();
+    let id = shelf.resolve(&id)?;
+    Ok(HttpResponse::Ok().json(shelf.get(id)))
+}
+
+// 本を丸ごと置き換える。ID は変えない
+// 本を変える経路では、取り違えないように ID を省略せずに指定させる
+#[put("/books/{id}")]
+async fn update_book(
+    state: web::Data<AppState>,
+    id: web::Path<Uuid>,
+    book: web::Json<Book>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let book = state.modify(|shelf| {
+        let old = shelf.replace_book(id, book.into_inner())?;
+        let book = shelf.get(id).cloned().ok_or(Error::BookNotFound(id))?;
+        Ok((book, Undo::Replace(old)))
+    })?;
+    Ok(HttpResponse::Ok().json(book))
+}
+
+// 本棚のファイルの隣に貸出の記録があれば、貸出中の本は取り除けず、予約は取り消す
+#[delete("/books/{id}")]
+async fn delete_book(
+    state: web::Data<AppState>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    state.modify(|shelf| {
+        let book = match &state.file {
+            Some(file) => lending::remove_book(file, shelf, id)?,
+            None => shelf.remove_book(id)?,
+        };
+        Ok(((), Undo::Restore(book)))
+    })?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// q は Bookshelf::search と同じクエリ (例: author:山田 rust)。スコアの高い順に返す
+#[get("/search")]
+async fn search(state: web::Data<AppState>, params: web::Query<SearchParams>) -> HttpResponse {
+    let page = Page {
+        offset: params.offset,
+        limit: params.limit,
+    };
+    let shelf = state.shelf.read().unwrap();
+    let found: Vec<SearchResult> = shelf
+        .search(&params.q)
+        .into_iter()
+        .map(|(score, book)| SearchResult { score, book })
+        .collect();
+    HttpResponse::Ok().json(Paged {
+        total: found.len(),
+        offset: page.offset,
+        limit: page.limit(),
+        items: page.slice(&found),
+    })
+}
+
+#[get("/openapi.json")]
+async fn openapi() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(OPENAPI)
+}
+
+// App::new().configure(config) のように使う
+pub fn config(cfg: &mut web::ServiceConfig) {
+    // 本文の JSON を読めないとき (ISBN が正しくないときも含む) も {"error": "..."} を返す
+    let json_config = web::JsonConfig::default().error_handler(|err, _| {
+        let response = HttpResponse::BadRequest().json(json!({ "error": err.to_string() }));
+        error::InternalError::from_response(err, response).into()
+    });
+    // 経路の ID が UUID でないときも同じ
+    let path_config = web::PathConfig::default().error_handler(|err, _| {
+        let response = HttpResponse::BadRequest().json(json!({ "error": err.to_string() }));
+        error::InternalError::from_response(err, response).into()
+    });
+    cfg.app_data(json_config)
+        .app_data(path_config)
+        .service(list_books)
+        .service(create_book)
+        .service(get_book)
+        .service(update_book)
+        .service(delete_book)
+        .service(search)
+        .service(openapi);
+}
@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+
+use actix_web::{web, App, HttpServer};
+use bookshelf_server::{config, AppState};
+
+// 使い方: bookshelf_server [本棚のファイル] [待ち受けるアドレス]
+// ファイルを省略したら bookshelf.json を使う (.db か .sqlite なら SQLite)
+// アドレスを省略したら 127.0.0.1:10000 で待ち受ける
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let file = args
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("bookshelf.json"));
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:10000".to_string());
+    let state = match AppState::open(file) {
+        Ok(state) => web::Data::new(state),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    HttpServer::new(move || App::new().configure(config).app_data(state.clone()))
+        .bind(addr)?
+        .run()
+        .await
+}
@@ -0,0 +1,242 @@
+use actix_web::{
+    http::{header, StatusCode},
+    test, web, App,
+};
+use bookshelf_server::{config, AppState};
+use my_library::library::{book::Book, bookshelf::Bookshelf, lending::Lending};
+use serde_json::{json, Value};
+
+// 本棚をファイルに保存しない状態で、サーバーをプロセスの中で動かす
+macro_rules! init_app {
+    ($shelf:expr) => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new($shelf)))
+                .configure(config),
+        )
+        .await
+    };
+}
+
+#[actix_web::test]
+async fn test_crud() {
+    let app = init_app!(Bookshelf::new());
+
+    let request = test::TestRequest::post()
+        .uri("/books")
+        .set_json(json!({
+            "title": "プログラミングRust",
+            "authors": ["Jim Blandy", "Jason Orendorff"],
+            "isbn": "4-87311-855-7",
+            "year": 2018
+        }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = response
+        .headers()
+        .get(header::LOCATION)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let book: Value = test::read_body_json(response).await;
+    assert_eq!(book["isbn"], "9784873118550");
+    assert_eq!(location, format!("/books/{}", book["id"].as_str().unwrap()));
+
+    // 同じ ISBN の本は追加できない
+    let request = test::TestRequest::post()
+        .uri("/books")
+        .set_json(json!({ "title": "重複", "authors": ["x"], "isbn": "9784873118550" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::CONFLICT
+    );
+    let request = test::TestRequest::post()
+        .uri("/books")
+        .set_json(json!({ "title": "不正", "authors": ["x"], "isbn": "1234" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let error: Value = test::read_body_json(response).await;
+    assert!(error["error"].as_str().unwrap().contains("ISBN"));
+
+    let request = test::TestRequest::put()
+        .uri(&location)
+        .set_json(json!({ "title": "プログラミングRust 第2版", "authors": ["Jim Blandy"] }))
+        .to_request();
+    let updated: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(updated["id"], book["id"]);
+    assert_eq!(updated["title"], "プログラミングRust 第2版");
+    assert!(updated.get("isbn").is_none());
+
+    let request = test::TestRequest::get().uri(&location).to_request();
+    let fetched: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(fetched, updated);
+
+    let request = test::TestRequest::delete().uri(&location).to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    let request = test::TestRequest::get().uri(&location).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let error: Value = test::read_body_json(response).await;
+    assert!(error["error"].is_string());
+}
+
+#[actix_web::test]
+async fn test_search_pagination() {
+    let books = (1..=25).map(|i| {
+        let tag = if i % 2 == 0 { "rust" } else { "python" };
+        Book::new(&format!("入門 第{}巻", i), "山田太郎").tag(tag)
+    });
+    let app = init_app!(Bookshelf::from_books(books).unwrap());
+
+    let request = test::TestRequest::get().uri("/books").to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(page["total"], 25);
+    assert_eq!(page["items"].as_array().unwrap().len(), 20);
+
+    let request = test::TestRequest::get()
+        .uri("/books?offset=20&limit=10")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(page["items"].as_array().unwrap().len(), 5);
+    assert_eq!(page["items"][0]["title"], "入門 第21巻");
+
+    let request = test::TestRequest::get()
+        .uri("/search?q=tag%3Arust&offset=10&limit=5")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(page["total"], 12);
+    assert_eq!(page["limit"], 5);
+    let items = page["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    assert!(items[0]["score"].as_i64().unwrap() > 0);
+    assert_eq!(items[0]["book"]["tags"][0], "rust");
+
+    // API の説明に、すべての経路が載っている
+    let request = test::TestRequest::get().uri("/openapi.json").to_request();
+    let openapi: Value = test::call_and_read_body_json(&app, request).await;
+    for path in ["/books", "/books/{id}", "/search"] {
+        assert!(openapi["paths"].get(path).is_some(), "{}", path);
+    }
+}
+
+#[actix_web::test]
+async fn test_full_id_required() {
+    let id = "0000aaaa-0000-4000-8000-000000000001";
+    let books = [id, "0000bbbb-0000-4000-8000-000000000002"].map(|id| Book {
+        id: id.parse().unwrap(),
+        ..Book::new("プログラミングRust", "Jim Blandy")
+    });
+    let app = init_app!(Bookshelf::from_books(books).unwrap());
+
+    // 読むだけなら先頭の数文字でよい
+    let request = test::TestRequest::get()
+        .uri(&format!("/books/{}", &id[..8]))
+        .to_request();
+    let fetched: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(fetched["id"], id);
+
+    // 変えるときは ID を省略できない
+    let request = test::TestRequest::delete()
+        .uri(&format!("/books/{}", &id[..8]))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let error: Value = test::read_body_json(response).await;
+    assert!(error["error"].is_string());
+
+    // 当てはまる本が複数あるときは 400
+    let request = test::TestRequest::get().uri("/books/0000").to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_open_saves_to_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("bookshelf.json");
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState::open(file.clone()).unwrap()))
+            .configure(config),
+    )
+    .await;
+
+    let request = test::TestRequest::post()
+        .uri("/books")
+        .set_json(json!({ "title": "プログラミングRust", "authors": ["Jim Blandy"] }))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, request).await;
+
+    // 保存したファイルから、同じ本を読み戻せる
+    let saved = Bookshelf::load(&file).unwrap();
+    let saved: Vec<&Book> = saved.books().collect();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].id.to_string(), book["id"].as_str().unwrap());
+
+    // 保存できなければエラーを返し、メモリ上の本棚も変えない
+    drop(dir);
+    let request = test::TestRequest::delete()
+        .uri(&format!("/books/{}", book["id"].as_str().unwrap()))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+    let request = test::TestRequest::get().uri("/books").to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(page["total"], 1);
+}
+
+#[actix_web::test]
+async fn test_delete_keeps_lending_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("bookshelf.json");
+    let mut lending = Lending::new(Bookshelf::new());
+    let rust = lending
+        .add_book(Book::new("プログラミングRust", "Jim Blandy"))
+        .unwrap();
+    let python = lending
+        .add_book(Book::new("Pythonプログラミング入門", "山田花子"))
+        .unwrap();
+    let taro = lending.add_member("山田太郎").unwrap();
+    let hanako = lending.add_member("山田花子").unwrap();
+    let today = "2024-04-01".parse().unwrap();
+    lending.checkout(rust, taro, today).unwrap();
+    lending.checkout(python, taro, today).unwrap();
+    lending.place_hold(python, hanako).unwrap();
+    lending.return_book(python, today).unwrap();
+    lending.save(&file).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState::open(file.clone()).unwrap()))
+            .configure(config),
+    )
+    .await;
+    // 貸出中の本は取り除けない
+    let request = test::TestRequest::delete()
+        .uri(&format!("/books/{}", rust))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::CONFLICT
+    );
+    // 予約だけの本は取り除け、予約も消える
+    let request = test::TestRequest::delete()
+        .uri(&format!("/books/{}", python))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    let lending = Lending::load(&file).unwrap();
+    assert!(lending.loan(rust).is_some());
+    assert!(lending.holds(python).is_empty());
+}
@@ -0,0 +1,97 @@
+// インデックスを使った検索と、すべての本を調べる検索を比べる
+//
+//   cargo bench -p my_library
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use my_library::library::{book::Book, bookshelf::Bookshelf, query::Query};
+
+const WORDS: &[&str] = &[
+    "Rust",
+    "Python",
+    "入門",
+    "実践",
+    "プログラミング",
+    "データ",
+    "分析",
+    "機械学習",
+    "Web",
+    "アプリ",
+    "設計",
+    "テスト",
+    "ゲーム",
+    "小説",
+    "歴史",
+    "料理",
+    "旅行",
+    "写真",
+];
+const AUTHORS: &[&str] = &[
+    "山田", "佐藤", "鈴木", "高橋", "田中", "伊藤", "渡辺", "中村",
+];
+const TAGS: &[&str] = &["技術書", "小説", "実用", "趣味"];
+
+// 乱数のクレートを使わずに、毎回同じ本棚を作る
+fn bookshelf(size: usize) -> Bookshelf {
+    let mut shelf = Bookshelf::new();
+    let mut seed: u64 = 42;
+    let mut next = |n: usize| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as usize % n
+    };
+    for i in 0..size {
+        let title = format!(
+            "{}{}{} 第{}版",
+            WORDS[next(WORDS.len())],
+            WORDS[next(WORDS.len())],
+            WORDS[next(WORDS.len())],
+            i
+        );
+        let author = format!("{}{}", AUTHORS[next(AUTHORS.len())], i % 1000);
+        let book = Book::new(&title, &author).tag(TAGS[next(TAGS.len())]);
+        shelf.add_book(book).unwrap();
+    }
+    shelf
+}
+
+fn search(c: &mut Criterion) {
+    for size in [1_000, 10_000, 100_000] {
+        let shelf = bookshelf(size);
+        let mut group = c.benchmark_group(format!("search/{}", size));
+        for text in ["第12345版", "author:山田 rust", "機械学習 tag:技術書"] {
+            let query = Query::parse(text);
+            group.bench_with_input(BenchmarkId::new("index", text), &query, |b, query| {
+                b.iter(|| shelf.search_query(black_box(query)).len())
+            });
+            group.bench_with_input(BenchmarkId::new("scan", text), &query, |b, query| {
+                b.iter(|| shelf.search_query_scan(black_box(query)).len())
+            });
+        }
+        // 完全一致は、ありそうなタイトルの1冊を探す
+        let title = shelf.books().nth(size / 2).unwrap().title.clone();
+        group.bench_function("partial/index", |b| {
+            b.iter(|| shelf.search_books_partial(black_box("第12345版")).len())
+        });
+        group.bench_function("partial/scan", |b| {
+            b.iter(|| {
+                shelf
+                    .search_books_partial_scan(black_box("第12345版"))
+                    .len()
+            })
+        });
+        group.bench_function("exact/index", |b| {
+            b.iter(|| shelf.search_books_exact(black_box(&title)).len())
+        });
+        group.bench_function("exact/scan", |b| {
+            b.iter(|| shelf.search_books_exact_scan(black_box(&title)).len())
+        });
+        group.finish();
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = search
+}
+criterion_main!(benches);
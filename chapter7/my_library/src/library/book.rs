@@ -1,14 +1,119 @@
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{error::Error, isbn::Isbn};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Book {
+    // 本棚の中で本を区別するための ID。同じ本が何冊あっても別の ID になる
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
     pub title: String,
-    pub author: String,
+    // タイトルの読み (かな)。漢字のタイトルを読みで検索できるようにする
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading: Option<String>,
+    pub authors: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isbn: Option<Isbn>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    // 出版年
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl Book {
     pub fn new(title: &str, author: &str) -> Self {
         Self {
+            id: Uuid::new_v4(),
             title: title.to_string(),
-            author: author.to_string(),
+            reading: None,
+            authors: vec![author.to_string()],
+            isbn: None,
+            publisher: None,
+            year: None,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn reading(mut self, reading: &str) -> Self {
+        self.reading = Some(reading.to_string());
+        self
+    }
+
+    // 共著者を追加する
+    pub fn author(mut self, author: &str) -> Self {
+        self.authors.push(author.to_string());
+        self
+    }
+
+    pub fn isbn(mut self, isbn: Isbn) -> Self {
+        self.isbn = Some(isbn);
+        self
+    }
+
+    pub fn publisher(mut self, publisher: &str) -> Self {
+        self.publisher = Some(publisher.to_string());
+        self
+    }
+
+    pub fn year(mut self, year: u16) -> Self {
+        self.year = Some(year);
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    // 本棚に入れる前に、空の項目がないか確かめる
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |message: &str| Err(Error::InvalidBook(message.to_string()));
+        if self.title.trim().is_empty() {
+            return invalid("タイトルが空です");
+        }
+        if self.authors.is_empty() || self.authors.iter().any(|a| a.trim().is_empty()) {
+            return invalid("著者が空です");
+        }
+        if self.reading.as_ref().is_some_and(|r| r.trim().is_empty()) {
+            return invalid("読みが空です");
+        }
+        if self.publisher.as_ref().is_some_and(|p| p.trim().is_empty()) {
+            return invalid("出版社が空です");
+        }
+        if self.year == Some(0) {
+            return invalid("出版年が 0 です");
         }
+        if self.tags.iter().any(|t| t.trim().is_empty()) {
+            return invalid("空のタグがあります");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_book_serde_round_trip() {
+        let book = Book::new("プログラミングRust", "Jim Blandy")
+            .author("Jason Orendorff")
+            .isbn("4-87311-855-7".parse().unwrap())
+            .publisher("オライリー・ジャパン")
+            .year(2018)
+            .tag("rust");
+        assert!(book.validate().is_ok());
+
+        let json = serde_json::to_string(&book).unwrap();
+        assert!(json.contains("\"isbn\":\"9784873118550\""), "{}", json);
+        assert_eq!(serde_json::from_str::<Book>(&json).unwrap(), book);
+
+        let json = r#"{"title": "t", "authors": ["a"], "isbn": "978-0-306-40615-8"}"#;
+        assert!(serde_json::from_str::<Book>(json).is_err());
+        assert!(Book::new(" ", "a").validate().is_err());
     }
 }
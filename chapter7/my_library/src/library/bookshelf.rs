@@ -1,10 +1,45 @@
+use std::collections::{HashMap, HashSet};
+
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use uuid::Uuid;
 
-use super::book::Book;
+use super::{
+    book::Book,
+    error::Error,
+    index::{NgramIndex, SearchKeys},
+    isbn::Isbn,
+    normalize::normalize,
+    query::{Field, Query, Term, Weights},
+};
 
 pub struct Bookshelf {
-    books: Vec<Book>,
+    // 取り除いた本は None にして、インデックスが指す位置をずらさない
+    entries: Vec<Option<Entry>>,
+    slots: HashMap<Uuid, usize>,
+    isbns: HashMap<Isbn, Uuid>,
+    index: NgramIndex,
     matcher: SkimMatcherV2,
+    weights: Weights,
+}
+
+#[derive(Clone)]
+struct Entry {
+    book: Book,
+    keys: SearchKeys,
+}
+
+// SkimMatcherV2 は Clone できないので、マッチャーだけ作り直す
+impl Clone for Bookshelf {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            slots: self.slots.clone(),
+            isbns: self.isbns.clone(),
+            index: self.index.clone(),
+            matcher: SkimMatcherV2::default(),
+            weights: self.weights,
+        }
+    }
 }
 
 impl Default for Bookshelf {
@@ -17,57 +52,445 @@ impl Bookshelf {
     pub fn new() -> Self {
         let matcher = SkimMatcherV2::default();
         Self {
-            books: Vec::new(),
+            entries: Vec::new(),
+            slots: HashMap::new(),
+            isbns: HashMap::new(),
+            index: NgramIndex::default(),
             matcher,
+            weights: Weights::default(),
+        }
+    }
+
+    // 保存しておいた本から本棚を作る。ID や ISBN が重複していればエラーにする
+    pub fn from_books(books: impl IntoIterator<Item = Book>) -> Result<Self, Error> {
+        let mut shelf = Self::new();
+        for book in books {
+            shelf.add_book(book)?;
+        }
+        Ok(shelf)
+    }
+
+    pub fn weights(mut self, weights: Weights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    // 本を追加するメソッド。同じ ISBN の本は追加できない
+    pub fn add_book(&mut self, book: Book) -> Result<Uuid, Error> {
+        book.validate()?;
+        if self.slots.contains_key(&book.id) {
+            return Err(Error::DuplicateId(book.id));
+        }
+        if let Some(isbn) = &book.isbn {
+            if self.isbns.contains_key(isbn) {
+                return Err(Error::DuplicateIsbn(isbn.clone()));
+            }
+            self.isbns.insert(isbn.clone(), book.id);
+        }
+        let id = book.id;
+        let slot = self.entries.len();
+        let keys = SearchKeys::new(&book);
+        self.index.insert(slot, &keys);
+        self.slots.insert(id, slot);
+        self.entries.push(Some(Entry { book, keys }));
+        Ok(id)
+    }
+
+    // 本の内容を置き換えるメソッド。ID と本棚の中の順番は変えない
+    pub fn replace_book(&mut self, id: Uuid, book: Book) -> Result<Book, Error> {
+        let book = Book { id, ..book };
+        book.validate()?;
+        let slot = *self.slots.get(&id).ok_or(Error::BookNotFound(id))?;
+        if let Some(isbn) = &book.isbn {
+            if self.isbns.get(isbn).is_some_and(|other| *other != id) {
+                return Err(Error::DuplicateIsbn(isbn.clone()));
+            }
+        }
+        let keys = SearchKeys::new(&book);
+        let isbn = book.isbn.clone();
+        let old = self.entries[slot].replace(Entry { book, keys }).unwrap();
+        // 古い内容の n-gram を消してから、新しい内容の n-gram を入れる
+        self.index.remove(slot, &old.keys);
+        if let Some(entry) = &self.entries[slot] {
+            self.index.insert(slot, &entry.keys);
+        }
+        if let Some(old_isbn) = &old.book.isbn {
+            self.isbns.remove(old_isbn);
+        }
+        if let Some(isbn) = isbn {
+            self.isbns.insert(isbn, id);
+        }
+        Ok(old.book)
+    }
+
+    // 本を取り除くメソッド
+    pub fn remove_book(&mut self, id: Uuid) -> Result<Book, Error> {
+        let slot = self.slots.remove(&id).ok_or(Error::BookNotFound(id))?;
+        let entry = self.entries[slot].take().unwrap();
+        self.index.remove(slot, &entry.keys);
+        if let Some(isbn) = &entry.book.isbn {
+            self.isbns.remove(isbn);
+        }
+        // 半分以上が空いたら詰めて、インデックスを作り直す
+        if self.slots.len() * 2 < self.entries.len() {
+            self.compact();
+        }
+        Ok(entry.book)
+    }
+
+    fn compact(&mut self) {
+        self.entries.retain(Option::is_some);
+        self.slots.clear();
+        self.index = NgramIndex::default();
+        for (slot, entry) in self.entries.iter().flatten().enumerate() {
+            self.slots.insert(entry.book.id, slot);
+            self.index.insert(slot, &entry.keys);
         }
     }
 
-    // 本を追加するメソッド
-    pub fn add_book(&mut self, book: Book) {
-        self.books.push(book);
+    fn entry(&self, slot: usize) -> &Entry {
+        self.entries[slot].as_ref().unwrap()
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&Book> {
+        self.slots.get(&id).map(|slot| &self.entry(*slot).book)
+    }
+
+    pub fn find_by_isbn(&self, isbn: &Isbn) -> Option<&Book> {
+        self.isbns.get(isbn).and_then(|id| self.get(*id))
+    }
+
+    // ID か、ID の先頭部分か、ISBN で本を探す
+    pub fn resolve(&self, key: &str) -> Result<Uuid, Error> {
+        if let Ok(isbn) = Isbn::parse(key) {
+            if let Some(book) = self.find_by_isbn(&isbn) {
+                return Ok(book.id);
+            }
+        }
+        let prefix = key.to_lowercase();
+        let mut found = self
+            .books()
+            .filter(|book| book.id.to_string().starts_with(&prefix) && !prefix.is_empty());
+        match (found.next(), found.next()) {
+            (Some(book), None) => Ok(book.id),
+            (Some(_), Some(_)) => Err(Error::AmbiguousId(key.to_string())),
+            (None, _) => Err(Error::NoMatchingBook(key.to_string())),
+        }
+    }
+
+    // 追加した順に返す
+    pub fn books(&self) -> impl Iterator<Item = &Book> {
+        self.entries.iter().flatten().map(|entry| &entry.book)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    // 本がある位置を、追加した順に返す
+    fn all_slots(&self) -> Vec<usize> {
+        (0..self.entries.len())
+            .filter(|slot| self.entries[*slot].is_some())
+            .collect()
+    }
+
+    fn sorted(slots: HashSet<usize>) -> Vec<usize> {
+        let mut slots: Vec<usize> = slots.into_iter().collect();
+        slots.sort_unstable();
+        slots
     }
 
     // タイトルで本を検索するメソッド
     pub fn search_books(&self, title_query: &str) -> Vec<&Book> {
-        let mut found_books = vec![];
-        for book in &self.books {
-            let match_result = self.matcher.fuzzy_match(&book.title, title_query);
-            if let Some(score) = match_result {
-                if score > 0 {
-                    found_books.push(book);
+        let query = Query {
+            terms: vec![Term {
+                field: Some(Field::Title),
+                text: title_query.to_string(),
+            }],
+        };
+        self.search_query(&query)
+            .into_iter()
+            .map(|(_, book)| book)
+            .collect()
+    }
+
+    // クエリ (query.rs を参照) で本を検索するメソッド
+    // 全角の ： や項目名も使えるように、分ける前に正規化する
+    pub fn search(&self, query: &str) -> Vec<(i64, &Book)> {
+        self.search_query(&Query::parse(&normalize(query)))
+    }
+
+    // スコアの高い順に返す。スコアが同じなら追加した順
+    pub fn search_query(&self, query: &Query) -> Vec<(i64, &Book)> {
+        let query = normalize_query(query);
+        self.rank(&query, self.candidates(&query))
+    }
+
+    // インデックスを使わずに、すべての本を調べる。ベンチマークで比べるためのもの
+    pub fn search_query_scan(&self, query: &Query) -> Vec<(i64, &Book)> {
+        let query = normalize_query(query);
+        self.rank(&query, self.all_slots())
+    }
+
+    // インデックスで、すべての語が一致しうる本に絞り込む
+    fn candidates(&self, query: &Query) -> Vec<usize> {
+        let mut candidates: Option<HashSet<usize>> = None;
+        for term in &query.terms {
+            let mut term_slots = HashSet::new();
+            for field in term.fields() {
+                match self.index.fuzzy_candidates(*field, &term.text) {
+                    Some(slots) => term_slots.extend(slots),
+                    None => return self.all_slots(),
                 }
             }
+            candidates = Some(match candidates {
+                Some(slots) => slots.intersection(&term_slots).copied().collect(),
+                None => term_slots,
+            });
+        }
+        match candidates {
+            Some(slots) => Self::sorted(slots),
+            None => self.all_slots(),
         }
+    }
+
+    fn rank(&self, query: &Query, slots: Vec<usize>) -> Vec<(i64, &Book)> {
+        let mut found_books: Vec<(i64, &Book)> = slots
+            .into_iter()
+            .map(|slot| self.entry(slot))
+            .filter_map(|entry| Some((self.score(&entry.keys, query)?, &entry.book)))
+            .collect();
+        found_books.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
         found_books
     }
 
+    // どれかの語が一致しなければ None
+    // 語ごとに、一致した項目のうち一番高いスコアを足していく
+    fn score(&self, keys: &SearchKeys, query: &Query) -> Option<i64> {
+        query
+            .terms
+            .iter()
+            .map(|term| {
+                term.fields()
+                    .iter()
+                    .filter_map(|field| self.field_score(keys, *field, &term.text))
+                    .max()
+            })
+            .sum()
+    }
+
+    fn field_score(&self, keys: &SearchKeys, field: Field, text: &str) -> Option<i64> {
+        keys.of(field)
+            .iter()
+            .filter_map(|value| self.matcher.fuzzy_match(value, text))
+            .filter(|score| *score > 0)
+            .max()
+            .map(|score| score * self.weights.of(field))
+    }
+
     // タイトル名の完全一致で本を検索するメソッド
     pub fn search_books_exact(&self, title_query: &str) -> Vec<&Book> {
-        self.books
-            .iter()
-            .filter(|book| book.title == title_query)
-            .collect()
+        let title_query = normalize(title_query);
+        self.exact_in(self.title_candidates(&title_query), &title_query)
     }
+
+    // インデックスを使わない完全一致の検索。ベンチマークで比べるためのもの
+    pub fn search_books_exact_scan(&self, title_query: &str) -> Vec<&Book> {
+        self.exact_in(self.all_slots(), &normalize(title_query))
+    }
+
     // タイトル名の部分一致で本を検索するメソッド
     pub fn search_books_partial(&self, title_query: &str) -> Vec<&Book> {
-        self.books
-            .iter()
-            .filter(|book| book.title.contains(title_query))
+        let title_query = normalize(title_query);
+        self.partial_in(self.title_candidates(&title_query), &title_query)
+    }
+
+    // インデックスを使わない部分一致の検索。ベンチマークで比べるためのもの
+    pub fn search_books_partial_scan(&self, title_query: &str) -> Vec<&Book> {
+        self.partial_in(self.all_slots(), &normalize(title_query))
+    }
+
+    fn exact_in(&self, slots: Vec<usize>, title_query: &str) -> Vec<&Book> {
+        slots
+            .into_iter()
+            .map(|slot| self.entry(slot))
+            .filter(|entry| entry.keys.title[0] == title_query)
+            .map(|entry| &entry.book)
+            .collect()
+    }
+
+    fn partial_in(&self, slots: Vec<usize>, title_query: &str) -> Vec<&Book> {
+        slots
+            .into_iter()
+            .map(|slot| self.entry(slot))
+            .filter(|entry| entry.keys.title.iter().any(|t| t.contains(title_query)))
+            .map(|entry| &entry.book)
             .collect()
     }
+
+    fn title_candidates(&self, title_query: &str) -> Vec<usize> {
+        match self.index.substring_candidates(Field::Title, title_query) {
+            Some(slots) => Self::sorted(slots),
+            None => self.all_slots(),
+        }
+    }
+}
+
+fn normalize_query(query: &Query) -> Query {
+    Query {
+        terms: query
+            .terms
+            .iter()
+            .map(|term| Term {
+                field: term.field,
+                text: normalize(&term.text),
+            })
+            .collect(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Book, Bookshelf};
+    use super::{Book, Bookshelf, Error, Query};
     #[test]
     fn test_bookshelf() {
         let mut shelf = Bookshelf::new();
         let book1 = Book::new("すごいぞChatGPT!AIを使って学ぼうRust!", "山田太郎");
         let book2 = Book::new("Pythonプログラミング入門", "山田花子");
-        shelf.add_book(book1);
-        shelf.add_book(book2);
+        shelf.add_book(book1).unwrap();
+        shelf.add_book(book2).unwrap();
         let found_books = shelf.search_books("chatgpt");
         println!("{:?}", found_books);
     }
+
+    #[test]
+    fn test_duplicate_isbn() {
+        let mut shelf = Bookshelf::new();
+        let book =
+            Book::new("プログラミングRust", "Jim Blandy").isbn("9784873118550".parse().unwrap());
+        let id = shelf.add_book(book).unwrap();
+        // ISBN-10 で書いても同じ本
+        let same =
+            Book::new("プログラミングRust", "Jim Blandy").isbn("4-87311-855-7".parse().unwrap());
+        assert!(matches!(shelf.add_book(same), Err(Error::DuplicateIsbn(_))));
+
+        let other = shelf.add_book(Book::new("Rust入門", "佐藤一郎")).unwrap();
+        let retitled = Book::new("プログラミングRust 第2版", "Jim Blandy")
+            .isbn("9784873118550".parse().unwrap());
+        assert!(matches!(
+            shelf.replace_book(other, retitled.clone()),
+            Err(Error::DuplicateIsbn(_))
+        ));
+        shelf.replace_book(id, retitled).unwrap();
+        assert_eq!(shelf.search_books_partial("第2版")[0].id, id);
+        assert!(shelf.search_books_exact("プログラミングRust").is_empty());
+        shelf.remove_book(other).unwrap();
+
+        assert_eq!(shelf.resolve("4-87311-855-7").unwrap(), id);
+        assert_eq!(shelf.resolve(&id.to_string()[..8]).unwrap(), id);
+        assert!(matches!(shelf.resolve(""), Err(Error::NoMatchingBook(_))));
+
+        assert_eq!(shelf.remove_book(id).unwrap().id, id);
+
+        assert!(matches!(shelf.remove_book(id), Err(Error::BookNotFound(i)) if i == id));
+        assert!(shelf.is_empty());
+    }
+
+    #[test]
+    fn test_search_ranking() {
+        let mut shelf = Bookshelf::new();
+        let python = Book::new("Pythonプログラミング入門", "山田花子").tag("rust");
+        let rust = Book::new("すごいぞChatGPT!AIを使って学ぼうRust!", "山田太郎");
+        let other = Book::new("Rustの本", "佐藤一郎");
+        let python = shelf.add_book(python).unwrap();
+        let rust = shelf.add_book(rust).unwrap();
+        shelf.add_book(other).unwrap();
+
+        // タイトルの一致はタグの一致より重い
+        let ids: Vec<_> = shelf
+            .search("author:山田 rust")
+            .iter()
+            .map(|(_, book)| book.id)
+            .collect();
+        assert_eq!(ids, vec![rust, python]);
+
+        let found = shelf.search("author:山田 rust");
+        assert!(found[0].0 > found[1].0);
+        assert!(shelf.search("author:鈴木").is_empty());
+    }
+
+    #[test]
+    fn test_index_matches_scan() {
+        let mut shelf = Bookshelf::new();
+        let mut ids = vec![];
+        for i in 0..40 {
+            let book = Book::new(&format!("Rust入門 第{}版", i), &format!("著者{}", i % 7)).tag(
+                if i % 3 == 0 {
+                    "プログラミング"
+                } else {
+                    "小説"
+                },
+            );
+            ids.push(shelf.add_book(book).unwrap());
+        }
+        // 詰め直しも起きるように、多めに取り除く
+        for id in ids.iter().step_by(2).chain(ids.iter().skip(1).step_by(4)) {
+            shelf.remove_book(*id).unwrap();
+        }
+        assert_eq!(shelf.len(), 10);
+
+        for text in [
+            "rust",
+            "第1",
+            "author:著者3",
+            "tag:ぷろぐらみんぐ 版",
+            "入門 小説",
+            "ない",
+        ] {
+            let query = Query::parse(text);
+            let ids = |found: Vec<(i64, &Book)>| -> Vec<_> {
+                found
+                    .iter()
+                    .map(|(score, book)| (*score, book.id))
+                    .collect()
+            };
+            assert_eq!(
+                ids(shelf.search_query(&query)),
+                ids(shelf.search_query_scan(&query)),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(shelf.search_books_partial("第1").len(), 3);
+        assert_eq!(shelf.search_books_partial_scan("第1").len(), 3);
+        assert_eq!(shelf.search_books_exact("rust入門 第9版").len(), 0);
+        assert_eq!(shelf.search_books_exact("rust入門 第11版").len(), 1);
+        assert_eq!(shelf.search_books_exact_scan("rust入門 第11版").len(), 1);
+    }
+
+    #[test]
+    fn test_search_normalized() {
+        let mut shelf = Bookshelf::new();
+        let book = Book::new("すごいぞChatGPT!AIを使って学ぼうRust!", "ヤマダタロウ")
+            .reading("すごいぞちゃっとじーぴーてぃー");
+        let id = shelf.add_book(book).unwrap();
+
+        for query in ["ＣｈａｔＧＰＴ", "ｽｺﾞｲｿﾞ", "ちゃっと", "ａｕｔｈｏｒ：やまだ"]
+        {
+            let found = shelf.search(query);
+            assert_eq!(found.len(), 1, "{}", query);
+            assert_eq!(found[0].1.id, id);
+        }
+        assert_eq!(shelf.search_books_partial("ｃｈａｔｇｐｔ").len(), 1);
+        assert_eq!(
+            shelf
+                .search_books_exact("すごいぞＣＨＡＴＧＰＴ！ＡＩを使って学ぼうＲＵＳＴ！")
+                .len(),
+            1
+        );
+    }
 }
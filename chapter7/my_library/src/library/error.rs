@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::isbn::Isbn;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("ISBN {isbn} は正しくありません: {reason}")]
+    InvalidIsbn { isbn: String, reason: String },
+    #[error("本の情報が正しくありません: {0}")]
+    InvalidBook(String),
+    #[error("ISBN {0} の本はもう本棚にあります")]
+    DuplicateIsbn(Isbn),
+    #[error("ID {0} の本はもう本棚にあります")]
+    DuplicateId(Uuid),
+    #[error("ID {0} の本は本棚にありません")]
+    BookNotFound(Uuid),
+    #[error("{0} に当てはまる本はありません")]
+    NoMatchingBook(String),
+    #[error("{0} に当てはまる本が複数あります。ID をもっと長く指定してください")]
+    AmbiguousId(String),
+    #[error("会員の情報が正しくありません: {0}")]
+    InvalidMember(String),
+    #[error("ID {0} の会員はいません")]
+    MemberNotFound(Uuid),
+    #[error("ID {0} の本は貸出中です")]
+    BookOnLoan(Uuid),
+    #[error("ID {0} の本は貸し出していません")]
+    NotOnLoan(Uuid),
+    #[error("ID {0} の本は、ほかの会員が予約しています")]
+    HeldForOther(Uuid),
+    #[error("会員 {member} はもう ID {book} の本を予約しています")]
+    AlreadyOnHold { book: Uuid, member: Uuid },
+    #[error("会員 {member} は ID {book} の本を予約していません")]
+    HoldNotFound { book: Uuid, member: Uuid },
+    #[error("会員 {member} は ID {book} の本を借りています")]
+    AlreadyBorrowed { book: Uuid, member: Uuid },
+    #[error("返却日 {returned} が貸出日 {borrowed} より前です")]
+    ReturnedBeforeBorrowed {
+        borrowed: NaiveDate,
+        returned: NaiveDate,
+    },
+    #[error("{today} から {days} 日後の返却期限は日付にできません")]
+    InvalidLoanDays { today: NaiveDate, days: u64 },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("{path} を読み書きできません: {message}")]
+    Json { path: PathBuf, message: String },
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    // line は見出しを1行目とした行番号
+    #[error("CSV の {line} 行目が正しくありません: {message}")]
+    Csv { line: u64, message: String },
+}
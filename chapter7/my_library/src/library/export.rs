@@ -0,0 +1,111 @@
+use serde_json::{json, Value};
+
+use super::book::Book;
+
+// BibTeX の @book 項目にする。キーは ID の32文字 (先頭だけでは本が多いと重なるため)
+pub fn bibtex<'a>(books: impl IntoIterator<Item = &'a Book>) -> String {
+    let mut output = String::new();
+    for book in books {
+        let mut fields = vec![
+            ("title", book.title.clone()),
+            ("author", book.authors.join(" and ")),
+        ];
+        if let Some(publisher) = &book.publisher {
+            fields.push(("publisher", publisher.clone()));
+        }
+        if let Some(year) = book.year {
+            fields.push(("year", year.to_string()));
+        }
+        if let Some(isbn) = &book.isbn {
+            fields.push(("isbn", isbn.to_string()));
+        }
+        if !book.tags.is_empty() {
+            fields.push(("keywords", book.tags.join(", ")));
+        }
+        output.push_str(&format!("@book{{{},\n", book.id.simple()));
+        for (name, value) in fields {
+            output.push_str(&format!("  {} = {{{}}},\n", name, escape_bibtex(&value)));
+        }
+        output.push_str("}\n\n");
+    }
+    output
+}
+
+// LaTeX で特別な意味を持つ文字を、そのまま表示されるようにする
+fn escape_bibtex(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '&' | '%' | '$' | '#' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// CSL-JSON (文献管理ソフトや pandoc が読める形式) の配列にする
+pub fn csl_json<'a>(books: impl IntoIterator<Item = &'a Book>) -> Value {
+    let items = books
+        .into_iter()
+        .map(|book| {
+            let mut item = json!({
+                "id": book.id.to_string(),
+                "type": "book",
+                "title": book.title,
+                // 姓と名を分けられないので、名前をそのまま使う
+                "author": book.authors.iter().map(|a| json!({ "literal": a })).collect::<Vec<_>>(),
+            });
+            if let Some(publisher) = &book.publisher {
+                item["publisher"] = json!(publisher);
+            }
+            if let Some(year) = book.year {
+                item["issued"] = json!({ "date-parts": [[year]] });
+            }
+            if let Some(isbn) = &book.isbn {
+                item["ISBN"] = json!(isbn.to_string());
+            }
+            if !book.tags.is_empty() {
+                item["keyword"] = json!(book.tags.join(", "));
+            }
+            item
+        })
+        .collect();
+    Value::Array(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export() {
+        let book = Book::new("Rust & WebAssembly 入門_2", "山田太郎")
+            .author("山田花子")
+            .isbn("9784873118550".parse().unwrap())
+            .year(2024)
+            .tag("rust");
+        let key = book.id.simple().to_string();
+
+        let bibtex = bibtex([&book]);
+        assert_eq!(
+            bibtex,
+            format!(
+                "@book{{{},\n  title = {{Rust \\& WebAssembly 入門\\_2}},\n  author = {{山田太郎 and 山田花子}},\n  year = {{2024}},\n  isbn = {{9784873118550}},\n  keywords = {{rust}},\n}}\n\n",
+                key
+            )
+        );
+
+        let csl = csl_json([&book]);
+        assert_eq!(csl[0]["type"], "book");
+        assert_eq!(csl[0]["author"][1]["literal"], "山田花子");
+        assert_eq!(csl[0]["issued"]["date-parts"][0][0], 2024);
+        assert_eq!(csl[0]["ISBN"], "9784873118550");
+        assert!(csl[0].get("publisher").is_none());
+    }
+}
@@ -0,0 +1,135 @@
+use std::{collections::HashSet, io::Read};
+
+use serde::Deserialize;
+
+use super::{book::Book, bookshelf::Bookshelf, error::Error};
+
+// 著者とタグは ; で区切って1つの列に書く
+//
+//   title,authors,isbn,publisher,year,tags,reading
+//   プログラミングRust,Jim Blandy;Jason Orendorff,978-4-87311-855-0,オライリー・ジャパン,2018,rust;技術書,
+//
+// title と authors 以外の列は省略できる
+#[derive(Debug, Deserialize)]
+struct Row {
+    title: String,
+    authors: String,
+    #[serde(default)]
+    isbn: String,
+    #[serde(default)]
+    publisher: String,
+    #[serde(default)]
+    year: Option<u16>,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    reading: String,
+}
+
+impl Row {
+    fn into_book(self) -> Result<Book, Error> {
+        let authors = split(&self.authors);
+        let mut book = Book::new(&self.title, authors.first().copied().unwrap_or(""));
+        for author in authors.iter().skip(1) {
+            book = book.author(author);
+        }
+        for tag in split(&self.tags) {
+            book = book.tag(tag);
+        }
+        if !self.isbn.trim().is_empty() {
+            book = book.isbn(self.isbn.parse()?);
+        }
+        if !self.publisher.trim().is_empty() {
+            book = book.publisher(self.publisher.trim());
+        }
+        if let Some(year) = self.year {
+            book = book.year(year);
+        }
+        if !self.reading.trim().is_empty() {
+            book = book.reading(self.reading.trim());
+        }
+        book.validate()?;
+        Ok(book)
+    }
+}
+
+fn split(values: &str) -> Vec<&str> {
+    values
+        .split(';')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+impl Bookshelf {
+    // CSV から本を追加し、追加した冊数を返す
+    // 途中の行が正しくなければ、1冊も追加しない
+    pub fn import_csv(&mut self, reader: impl Read) -> Result<usize, Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let mut books = vec![];
+        let mut isbns = HashSet::new();
+        let csv_error = |line: u64, message: String| Error::Csv { line, message };
+        let to_csv_error = |e: csv::Error| {
+            let line = e.position().map_or(0, |p| p.line());
+            csv_error(line, e.to_string())
+        };
+        let headers = reader.headers().map_err(to_csv_error)?.clone();
+        for result in reader.records() {
+            let record = result.map_err(to_csv_error)?;
+            let line = record.position().map_or(0, |p| p.line());
+            let book = record
+                .deserialize::<Row>(Some(&headers))
+                .map_err(|e| csv_error(line, e.to_string()))?
+                .into_book()
+                .map_err(|e| csv_error(line, e.to_string()))?;
+            if let Some(isbn) = &book.isbn {
+                if self.find_by_isbn(isbn).is_some() || !isbns.insert(isbn.clone()) {
+                    return Err(csv_error(
+                        line,
+                        Error::DuplicateIsbn(isbn.clone()).to_string(),
+                    ));
+                }
+            }
+            books.push(book);
+        }
+        let count = books.len();
+        for book in books {
+            self.add_book(book)?;
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_csv() {
+        let mut shelf = Bookshelf::new();
+        let csv = "\
+title,authors,isbn,year,tags
+プログラミングRust,Jim Blandy; Jason Orendorff,978-4-87311-855-0,2018,rust;技術書
+Pythonプログラミング入門,山田花子,,,
+";
+        assert_eq!(shelf.import_csv(csv.as_bytes()).unwrap(), 2);
+        let book = shelf.search("jason").pop().unwrap().1;
+        assert_eq!(book.authors, vec!["Jim Blandy", "Jason Orendorff"]);
+        assert_eq!(book.tags, vec!["rust", "技術書"]);
+        assert_eq!(book.year, Some(2018));
+
+        // 3行目の ISBN が本棚の本と重なるので、2行目も追加しない
+        let csv = "\
+title,authors,isbn
+Rust入門,佐藤一郎,
+プログラミングRust,Jim Blandy,4-87311-855-7
+";
+        assert!(matches!(
+            shelf.import_csv(csv.as_bytes()),
+            Err(Error::Csv { line: 3, .. })
+        ));
+        assert_eq!(shelf.len(), 2);
+    }
+}
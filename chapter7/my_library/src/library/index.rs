@@ -0,0 +1,118 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{book::Book, normalize::normalize, query::Field};
+
+// 検索用に正規化した本の項目
+#[derive(Clone)]
+pub(crate) struct SearchKeys {
+    // 読みがあれば、タイトルと一緒に探す
+    pub title: Vec<String>,
+    pub authors: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+impl SearchKeys {
+    pub fn new(book: &Book) -> Self {
+        let normalize_all = |values: &[String]| values.iter().map(|v| normalize(v)).collect();
+        Self {
+            title: std::iter::once(&book.title)
+                .chain(&book.reading)
+                .map(|v| normalize(v))
+                .collect(),
+            authors: normalize_all(&book.authors),
+            tags: normalize_all(&book.tags),
+        }
+    }
+
+    pub fn of(&self, field: Field) -> &[String] {
+        match field {
+            Field::Title => &self.title,
+            Field::Author => &self.authors,
+            Field::Tag => &self.tags,
+        }
+    }
+}
+
+// 1文字 (2文字目は '\0') か、連続する2文字
+type Gram = [char; 2];
+
+// 項目ごとの n-gram (1-gram と 2-gram) から、それを含む本の位置への転置インデックス
+//
+// あいまい検索では、クエリのすべての文字を含む本を 1-gram で候補にする
+// 部分一致では、クエリのすべての 2-gram を含む本を候補にする
+// どちらも一致する本を取りこぼさないので、候補だけを調べれば全件を調べたのと同じ結果になる
+#[derive(Clone, Default)]
+pub(crate) struct NgramIndex {
+    postings: HashMap<(Field, Gram), HashSet<usize>>,
+}
+
+impl NgramIndex {
+    pub fn insert(&mut self, slot: usize, keys: &SearchKeys) {
+        for gram in all_grams(keys) {
+            self.postings.entry(gram).or_default().insert(slot);
+        }
+    }
+
+    pub fn remove(&mut self, slot: usize, keys: &SearchKeys) {
+        for gram in all_grams(keys) {
+            if let Some(slots) = self.postings.get_mut(&gram) {
+                slots.remove(&slot);
+                if slots.is_empty() {
+                    self.postings.remove(&gram);
+                }
+            }
+        }
+    }
+
+    // text のすべての文字を含む本。text が空なら None (絞り込まない)
+    pub fn fuzzy_candidates(&self, field: Field, text: &str) -> Option<HashSet<usize>> {
+        let grams: HashSet<Gram> = text.chars().map(|c| [c, '\0']).collect();
+        self.intersect(field, grams)
+    }
+
+    // text を部分文字列として含みうる本。text が空なら None (絞り込まない)
+    pub fn substring_candidates(&self, field: Field, text: &str) -> Option<HashSet<usize>> {
+        let chars: Vec<char> = text.chars().collect();
+        let grams: HashSet<Gram> = if chars.len() == 1 {
+            HashSet::from([[chars[0], '\0']])
+        } else {
+            chars.windows(2).map(|w| [w[0], w[1]]).collect()
+        };
+        self.intersect(field, grams)
+    }
+
+    // 一番短い転置リストから始めて、ほかのリストにない本を落としていく
+    fn intersect(&self, field: Field, grams: HashSet<Gram>) -> Option<HashSet<usize>> {
+        if grams.is_empty() {
+            return None;
+        }
+        let mut lists = vec![];
+        for gram in grams {
+            match self.postings.get(&(field, gram)) {
+                Some(slots) => lists.push(slots),
+                None => return Some(HashSet::new()),
+            }
+        }
+        lists.sort_by_key(|slots| slots.len());
+        let (first, rest) = lists.split_first().unwrap();
+        Some(
+            first
+                .iter()
+                .filter(|slot| rest.iter().all(|slots| slots.contains(slot)))
+                .copied()
+                .collect(),
+        )
+    }
+}
+
+fn all_grams(keys: &SearchKeys) -> HashSet<(Field, Gram)> {
+    let mut grams = HashSet::new();
+    for field in Field::ALL {
+        for value in keys.of(field) {
+            let chars: Vec<char> = value.chars().collect();
+            grams.extend(chars.iter().map(|c| (field, [*c, '\0'])));
+            grams.extend(chars.windows(2).map(|w| (field, [w[0], w[1]])));
+        }
+    }
+    grams
+}
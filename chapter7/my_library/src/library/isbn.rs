@@ -0,0 +1,156 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::error::Error;
+
+// ISBN。ISBN-10 で作っても ISBN-13 に変換して持つ
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Isbn(String);
+
+impl Isbn {
+    // ハイフンと空白を除いてから、桁数とチェックディジットを確かめる
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::InvalidIsbn {
+            isbn: s.to_string(),
+            reason: reason.to_string(),
+        };
+        let chars: Vec<char> = s
+            .chars()
+            .filter(|c| !matches!(c, '-' | ' '))
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        match chars.len() {
+            10 => {
+                let digits = digits_of(&chars[..9]).ok_or_else(|| invalid("数字ではありません"))?;
+                let check = match chars[9] {
+                    'X' => 10,
+                    c => c
+                        .to_digit(10)
+                        .ok_or_else(|| invalid("数字ではありません"))?,
+                };
+                if check != isbn10_check_digit(&digits) {
+                    return Err(invalid("チェックディジットが合いません"));
+                }
+                let mut digits13 = vec![9, 7, 8];
+                digits13.extend_from_slice(&digits);
+                digits13.push(isbn13_check_digit(&digits13));
+                Ok(Self(to_string(&digits13)))
+            }
+            13 => {
+                let digits = digits_of(&chars).ok_or_else(|| invalid("数字ではありません"))?;
+                if !(digits.starts_with(&[9, 7, 8]) || digits.starts_with(&[9, 7, 9])) {
+                    return Err(invalid("978 か 979 で始まっていません"));
+                }
+                if digits[12] != isbn13_check_digit(&digits[..12]) {
+                    return Err(invalid("チェックディジットが合いません"));
+                }
+                Ok(Self(to_string(&digits)))
+            }
+            _ => Err(invalid("10桁か13桁ではありません")),
+        }
+    }
+
+    // ハイフンのない13桁
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    // ISBN-10 に変換する。979 で始まる ISBN には ISBN-10 がない
+    pub fn to_isbn10(&self) -> Option<String> {
+        let digits: Vec<u32> = self.0.chars().filter_map(|c| c.to_digit(10)).collect();
+        if !digits.starts_with(&[9, 7, 8]) {
+            return None;
+        }
+        let body = &digits[3..12];
+        let check = match isbn10_check_digit(body) {
+            10 => 'X',
+            d => char::from_digit(d, 10).unwrap(),
+        };
+        Some(format!("{}{}", to_string(body), check))
+    }
+}
+
+fn digits_of(chars: &[char]) -> Option<Vec<u32>> {
+    chars.iter().map(|c| c.to_digit(10)).collect()
+}
+
+fn to_string(digits: &[u32]) -> String {
+    digits
+        .iter()
+        .map(|d| char::from_digit(*d, 10).unwrap())
+        .collect()
+}
+
+// 先頭9桁から ISBN-10 のチェックディジット (0〜10、10 は X) を求める
+fn isbn10_check_digit(digits: &[u32]) -> u32 {
+    let sum: u32 = digits
+        .iter()
+        .zip((2..=10).rev())
+        .map(|(d, weight)| d * weight)
+        .sum();
+    (11 - sum % 11) % 11
+}
+
+// 先頭12桁から ISBN-13 のチェックディジットを求める
+fn isbn13_check_digit(digits: &[u32]) -> u32 {
+    let sum: u32 = digits
+        .iter()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { *d } else { d * 3 })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+impl FromStr for Isbn {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for Isbn {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s)
+    }
+}
+
+impl From<Isbn> for String {
+    fn from(isbn: Isbn) -> Self {
+        isbn.0
+    }
+}
+
+impl fmt::Display for Isbn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_convert() {
+        let isbn13: Isbn = "978-0-306-40615-7".parse().unwrap();
+        let isbn10: Isbn = "0-306-40615-2".parse().unwrap();
+        assert_eq!(isbn13, isbn10);
+        assert_eq!(isbn13.as_str(), "9780306406157");
+        assert_eq!(isbn13.to_isbn10().as_deref(), Some("0306406152"));
+
+        // チェックディジットが X になる ISBN-10
+        let isbn: Isbn = "0-8044-2957-x".parse().unwrap();
+        assert_eq!(isbn.as_str(), "9780804429573");
+        assert_eq!(isbn.to_isbn10().as_deref(), Some("080442957X"));
+
+        assert_eq!(Isbn::parse("979-10-90636-07-1").unwrap().to_isbn10(), None);
+        assert!(Isbn::parse("978-0-306-40615-8").is_err());
+        assert!(Isbn::parse("0-306-40615-3").is_err());
+        assert!(Isbn::parse("12345").is_err());
+    }
+}
@@ -0,0 +1,550 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+};
+
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{
+    book::Book,
+    bookshelf::Bookshelf,
+    error::Error,
+    store::{with_suffix, write_atomic},
+};
+
+// 貸出期間の既定値 (日)
+pub const DEFAULT_LOAN_DAYS: u64 = 14;
+// 会員や貸出の記録は、本棚のファイルの隣の {本棚のファイル}.lending.json に保存する
+pub const LENDING_SUFFIX: &str = ".lending.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Member {
+    pub id: Uuid,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Loan {
+    pub book_id: Uuid,
+    pub member_id: Uuid,
+    pub borrowed_on: NaiveDate,
+    pub due: NaiveDate,
+    // 返却するまでは None
+    pub returned_on: Option<NaiveDate>,
+}
+
+impl Loan {
+    // 返却期限を過ぎた日数。返却済みなら返却した日で数える
+    pub fn days_overdue(&self, today: NaiveDate) -> i64 {
+        let end = self.returned_on.unwrap_or(today);
+        (end - self.due).num_days().max(0)
+    }
+}
+
+// 本棚の本の貸出・返却・予約を管理する
+//
+// 予約のある本は、予約の先頭の会員しか借りられない
+// 返却されると、次に予約している会員が借りられるようになる
+pub struct Lending {
+    shelf: Bookshelf,
+    members: HashMap<Uuid, Member>,
+    // 本の ID ごとの、貸出中の記録
+    loans: HashMap<Uuid, Loan>,
+    // 返却済みの記録
+    history: Vec<Loan>,
+    // 本の ID ごとの、予約した会員の ID。先に予約した順に並べる
+    holds: HashMap<Uuid, VecDeque<Uuid>>,
+    loan_days: u64,
+}
+
+// 保存するときの形。出力が毎回同じになるように、本や会員の ID の順に並べる
+#[derive(Serialize, Deserialize)]
+struct LendingFile {
+    loan_days: u64,
+    members: Vec<Member>,
+    loans: Vec<Loan>,
+    history: Vec<Loan>,
+    holds: Vec<Hold>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Hold {
+    book_id: Uuid,
+    member_ids: Vec<Uuid>,
+}
+
+impl Lending {
+    pub fn new(shelf: Bookshelf) -> Self {
+        Self {
+            shelf,
+            members: HashMap::new(),
+            loans: HashMap::new(),
+            history: Vec::new(),
+            holds: HashMap::new(),
+            loan_days: DEFAULT_LOAN_DAYS,
+        }
+    }
+
+    pub fn loan_days(mut self, days: u64) -> Self {
+        self.loan_days = days;
+        self
+    }
+
+    // 本棚と、隣に置いた貸出の記録を読む。貸出の記録がなければ、誰も借りていないことにする
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut lending = Self::new(Bookshelf::load(path)?);
+        let lending_path = lending_path(path);
+        if !lending_path.exists() {
+            return Ok(lending);
+        }
+        let file = LendingFile::read(&lending_path)?;
+        lending.loan_days = file.loan_days;
+        for member in file.members {
+            lending.members.insert(member.id, member);
+        }
+        // 本棚から消えた本や、いない会員の記録があれば、読んだ貸出の記録は使えない
+        for loan in file.loans {
+            lending.ensure_exists(loan.book_id, loan.member_id)?;
+            if lending.loans.insert(loan.book_id, loan.clone()).is_some() {
+                return Err(Error::BookOnLoan(loan.book_id));
+            }
+        }
+        for hold in file.holds {
+            for member_id in &hold.member_ids {
+                lending.ensure_exists(hold.book_id, *member_id)?;
+            }
+            if !hold.member_ids.is_empty() {
+                lending.holds.insert(hold.book_id, hold.member_ids.into());
+            }
+        }
+        lending.history = file.history;
+        Ok(lending)
+    }
+
+    // 本棚を path に、貸出の記録をその隣に保存する
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        self.shelf.save(path)?;
+        let mut members: Vec<Member> = self.members.values().cloned().collect();
+        members.sort_by_key(|member| member.id);
+        let mut loans: Vec<Loan> = self.loans.values().cloned().collect();
+        loans.sort_by_key(|loan| loan.book_id);
+        let mut holds: Vec<Hold> = self
+            .holds
+            .iter()
+            .map(|(book_id, queue)| Hold {
+                book_id: *book_id,
+                member_ids: queue.iter().copied().collect(),
+            })
+            .collect();
+        holds.sort_by_key(|hold| hold.book_id);
+        let file = LendingFile {
+            loan_days: self.loan_days,
+            members,
+            loans,
+            history: self.history.clone(),
+            holds,
+        };
+        file.write(&lending_path(path))
+    }
+
+    pub fn shelf(&self) -> &Bookshelf {
+        &self.shelf
+    }
+
+    pub fn add_book(&mut self, book: Book) -> Result<Uuid, Error> {
+        self.shelf.add_book(book)
+    }
+
+    // 貸出中の本は取り除けない。予約は取り消す
+    pub fn remove_book(&mut self, book_id: Uuid) -> Result<Book, Error> {
+        if self.loans.contains_key(&book_id) {
+            return Err(Error::BookOnLoan(book_id));
+        }
+        let book = self.shelf.remove_book(book_id)?;
+        self.holds.remove(&book_id);
+        Ok(book)
+    }
+
+    pub fn add_member(&mut self, name: &str) -> Result<Uuid, Error> {
+        if name.trim().is_empty() {
+            return Err(Error::InvalidMember("名前が空です".to_string()));
+        }
+        let member = Member {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+        };
+        let id = member.id;
+        self.members.insert(id, member);
+        Ok(id)
+    }
+
+    pub fn member(&self, member_id: Uuid) -> Option<&Member> {
+        self.members.get(&member_id)
+    }
+
+    fn ensure_exists(&self, book_id: Uuid, member_id: Uuid) -> Result<(), Error> {
+        if self.shelf.get(book_id).is_none() {
+            return Err(Error::BookNotFound(book_id));
+        }
+        if !self.members.contains_key(&member_id) {
+            return Err(Error::MemberNotFound(member_id));
+        }
+        Ok(())
+    }
+
+    // today から貸出期間が過ぎた日。日付の範囲を超えるならエラーにする
+    fn due(&self, today: NaiveDate) -> Result<NaiveDate, Error> {
+        today
+            .checked_add_days(Days::new(self.loan_days))
+            .ok_or(Error::InvalidLoanDays {
+                today,
+                days: self.loan_days,
+            })
+    }
+
+    // 本を貸し出す。予約の先頭の会員が借りたら、その予約は消す
+    pub fn checkout(
+        &mut self,
+        book_id: Uuid,
+        member_id: Uuid,
+        today: NaiveDate,
+    ) -> Result<&Loan, Error> {
+        self.ensure_exists(book_id, member_id)?;
+        if self.loans.contains_key(&book_id) {
+            return Err(Error::BookOnLoan(book_id));
+        }
+        let due = self.due(today)?;
+        if let Some(queue) = self.holds.get_mut(&book_id) {
+            match queue.front() {
+                Some(first) if *first != member_id => return Err(Error::HeldForOther(book_id)),
+                Some(_) => {
+                    queue.pop_front();
+                }
+                None => {}
+            }
+            if queue.is_empty() {
+                self.holds.remove(&book_id);
+            }
+        }
+        let loan = Loan {
+            book_id,
+            member_id,
+            borrowed_on: today,
+            due,
+            returned_on: None,
+        };
+        Ok(self.loans.entry(book_id).or_insert(loan))
+    }
+
+    // 借りた日より前の日には返却できない
+    pub fn return_book(&mut self, book_id: Uuid, today: NaiveDate) -> Result<Loan, Error> {
+        let borrowed = self
+            .loans
+            .get(&book_id)
+            .ok_or(Error::NotOnLoan(book_id))?
+            .borrowed_on;
+        if today < borrowed {
+            return Err(Error::ReturnedBeforeBorrowed {
+                borrowed,
+                returned: today,
+            });
+        }
+        let mut loan = self
+            .loans
+            .remove(&book_id)
+            .ok_or(Error::NotOnLoan(book_id))?;
+        loan.returned_on = Some(today);
+        self.history.push(loan.clone());
+        Ok(loan)
+    }
+
+    // 返却期限を今日から貸出期間だけ延ばす。予約があれば延ばせない
+    pub fn renew(&mut self, book_id: Uuid, today: NaiveDate) -> Result<&Loan, Error> {
+        if self.holds.contains_key(&book_id) {
+            return Err(Error::HeldForOther(book_id));
+        }
+        let due = self.due(today)?;
+        let loan = self
+            .loans
+            .get_mut(&book_id)
+            .ok_or(Error::NotOnLoan(book_id))?;
+        loan.due = due;
+        Ok(loan)
+    }
+
+    // 予約の列の最後に並ぶ。借りている本は予約できない
+    pub fn place_hold(&mut self, book_id: Uuid, member_id: Uuid) -> Result<usize, Error> {
+        self.ensure_exists(book_id, member_id)?;
+        if self
+            .loans
+            .get(&book_id)
+            .is_some_and(|loan| loan.member_id == member_id)
+        {
+            return Err(Error::AlreadyBorrowed {
+                book: book_id,
+                member: member_id,
+            });
+        }
+        let queue = self.holds.entry(book_id).or_default();
+        if queue.contains(&member_id) {
+            return Err(Error::AlreadyOnHold {
+                book: book_id,
+                member: member_id,
+            });
+        }
+        queue.push_back(member_id);
+        Ok(queue.len())
+    }
+
+    pub fn cancel_hold(&mut self, book_id: Uuid, member_id: Uuid) -> Result<(), Error> {
+        let not_found = || Error::HoldNotFound {
+            book: book_id,
+            member: member_id,
+        };
+        let queue = self.holds.get_mut(&book_id).ok_or_else(not_found)?;
+        let position = queue
+            .iter()
+            .position(|id| *id == member_id)
+            .ok_or_else(not_found)?;
+        queue.remove(position);
+        if queue.is_empty() {
+            self.holds.remove(&book_id);
+        }
+        Ok(())
+    }
+
+    // 予約した会員を、先に予約した順に返す
+    pub fn holds(&self, book_id: Uuid) -> Vec<Uuid> {
+        self.holds
+            .get(&book_id)
+            .map(|queue| queue.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn loan(&self, book_id: Uuid) -> Option<&Loan> {
+        self.loans.get(&book_id)
+    }
+
+    // 会員が借りている本。返却期限の早い順
+    pub fn loans_of(&self, member_id: Uuid) -> Vec<&Loan> {
+        let mut loans: Vec<&Loan> = self
+            .loans
+            .values()
+            .filter(|loan| loan.member_id == member_id)
+            .collect();
+        loans.sort_by_key(|loan| (loan.due, loan.book_id));
+        loans
+    }
+
+    pub fn history(&self) -> &[Loan] {
+        &self.history
+    }
+
+    // 返却期限を過ぎた貸出。期限の早い順
+    pub fn overdue(&self, today: NaiveDate) -> Vec<&Loan> {
+        let mut loans: Vec<&Loan> = self
+            .loans
+            .values()
+            .filter(|loan| loan.due < today)
+            .collect();
+        loans.sort_by_key(|loan| (loan.due, loan.book_id));
+        loans
+    }
+}
+
+impl LendingFile {
+    fn read(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| Error::Json {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })
+    }
+
+    fn write(&self, path: &Path) -> Result<(), Error> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| Error::Json {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        write_atomic(path, &(json + "\n"))
+    }
+}
+
+fn lending_path(path: &Path) -> PathBuf {
+    with_suffix(path, LENDING_SUFFIX)
+}
+
+// path に保存する本棚 shelf から本を取り除く。本棚そのものは呼び出し側で保存する
+// 隣に貸出の記録があれば Lending::remove_book と同じく、貸出中の本は取り除けず、予約は取り消す
+// 貸出の記録を無視して取り除くと、Lending::load で読めなくなるため
+pub fn remove_book(path: &Path, shelf: &mut Bookshelf, book_id: Uuid) -> Result<Book, Error> {
+    let lending_path = lending_path(path);
+    if !lending_path.exists() {
+        return shelf.remove_book(book_id);
+    }
+    let mut file = LendingFile::read(&lending_path)?;
+    if file.loans.iter().any(|loan| loan.book_id == book_id) {
+        return Err(Error::BookOnLoan(book_id));
+    }
+    let book = shelf.remove_book(book_id)?;
+    let holds = file.holds.len();
+    file.holds.retain(|hold| hold.book_id != book_id);
+    if file.holds.len() != holds {
+        file.write(&lending_path)?;
+    }
+    Ok(book)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 4, day).unwrap()
+    }
+
+    fn lending() -> (Lending, Uuid, Uuid, Uuid) {
+        let mut lending = Lending::new(Bookshelf::new()).loan_days(7);
+        let book = lending
+            .add_book(Book::new("プログラミングRust", "Jim Blandy"))
+            .unwrap();
+        let taro = lending.add_member("山田太郎").unwrap();
+        let hanako = lending.add_member("山田花子").unwrap();
+        (lending, book, taro, hanako)
+    }
+
+    #[test]
+    fn test_checkout_and_return() {
+        let (mut lending, book, taro, hanako) = lending();
+        assert_eq!(lending.checkout(book, taro, date(1)).unwrap().due, date(8));
+        assert!(matches!(
+            lending.checkout(book, hanako, date(2)),
+            Err(Error::BookOnLoan(_))
+        ));
+        assert!(matches!(
+            lending.remove_book(book),
+            Err(Error::BookOnLoan(_))
+        ));
+        assert!(matches!(
+            lending.checkout(book, Uuid::new_v4(), date(2)),
+            Err(Error::MemberNotFound(_))
+        ));
+
+        assert!(lending.overdue(date(8)).is_empty());
+        assert_eq!(lending.overdue(date(10))[0].days_overdue(date(10)), 2);
+        assert_eq!(lending.loans_of(taro).len(), 1);
+
+        let loan = lending.return_book(book, date(9)).unwrap();
+        assert_eq!(loan.days_overdue(date(20)), 1);
+        assert!(matches!(
+            lending.return_book(book, date(9)),
+            Err(Error::NotOnLoan(_))
+        ));
+        assert_eq!(lending.history().len(), 1);
+        assert!(lending.overdue(date(20)).is_empty());
+
+        // 借りた日より前には返却できず、貸出は残る
+        lending.checkout(book, taro, date(10)).unwrap();
+        assert!(matches!(
+            lending.return_book(book, date(9)),
+            Err(Error::ReturnedBeforeBorrowed { .. })
+        ));
+        assert!(lending.loan(book).is_some());
+    }
+
+    #[test]
+    fn test_loan_days_overflow() {
+        let (lending, book, taro, _) = lending();
+        let mut lending = lending.loan_days(u64::MAX);
+        assert!(matches!(
+            lending.checkout(book, taro, date(1)),
+            Err(Error::InvalidLoanDays { .. })
+        ));
+        assert!(lending.loan(book).is_none());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("books.json");
+        let (mut lending, book, taro, hanako) = lending();
+        let other = lending
+            .add_book(Book::new("Pythonプログラミング入門", "山田花子"))
+            .unwrap();
+        lending.checkout(other, hanako, date(1)).unwrap();
+        lending.return_book(other, date(3)).unwrap();
+        lending.checkout(book, taro, date(2)).unwrap();
+        lending.place_hold(book, hanako).unwrap();
+        lending.save(&path).unwrap();
+
+        let loaded = Lending::load(&path).unwrap();
+        assert_eq!(loaded.shelf().len(), 2);
+        assert_eq!(loaded.member(taro).unwrap().name, "山田太郎");
+        assert_eq!(loaded.loan(book).unwrap().due, date(9));
+        assert_eq!(loaded.holds(book), vec![hanako]);
+        assert_eq!(loaded.history(), lending.history());
+
+        // 本棚のファイルから取り除くときも、貸出中の本は取り除けず、予約は取り消す
+        let mut shelf = Bookshelf::load(&path).unwrap();
+        assert!(matches!(
+            remove_book(&path, &mut shelf, book),
+            Err(Error::BookOnLoan(_))
+        ));
+        lending.return_book(book, date(4)).unwrap();
+        lending.save(&path).unwrap();
+        remove_book(&path, &mut shelf, book).unwrap();
+        shelf.save(&path).unwrap();
+        let loaded = Lending::load(&path).unwrap();
+        assert!(loaded.holds(book).is_empty());
+        assert_eq!(loaded.history().len(), 2);
+
+        // 貸出中の本が本棚から消えていたら読まない
+        let mut lending = loaded;
+        lending.checkout(other, taro, date(5)).unwrap();
+        lending.save(&path).unwrap();
+        let mut shelf = Bookshelf::load(&path).unwrap();
+        shelf.remove_book(other).unwrap();
+        shelf.save(&path).unwrap();
+        assert!(matches!(Lending::load(&path), Err(Error::BookNotFound(_))));
+    }
+
+    #[test]
+    fn test_holds_queue() {
+        let (mut lending, book, taro, hanako) = lending();
+        let jiro = lending.add_member("山田次郎").unwrap();
+        lending.checkout(book, taro, date(1)).unwrap();
+        assert!(matches!(
+            lending.place_hold(book, taro),
+            Err(Error::AlreadyBorrowed { .. })
+        ));
+        assert_eq!(lending.place_hold(book, hanako).unwrap(), 1);
+        assert_eq!(lending.place_hold(book, jiro).unwrap(), 2);
+        assert!(matches!(
+            lending.place_hold(book, hanako),
+            Err(Error::AlreadyOnHold { .. })
+        ));
+        // 予約があると延長できない
+        assert!(matches!(
+            lending.renew(book, date(5)),
+            Err(Error::HeldForOther(_))
+        ));
+
+        lending.return_book(book, date(5)).unwrap();
+        // 予約の先頭の花子さんしか借りられない
+        assert!(matches!(
+            lending.checkout(book, jiro, date(5)),
+            Err(Error::HeldForOther(_))
+        ));
+        lending.checkout(book, hanako, date(5)).unwrap();
+        assert_eq!(lending.holds(book), vec![jiro]);
+
+        lending.cancel_hold(book, jiro).unwrap();
+        assert!(matches!(
+            lending.cancel_hold(book, jiro),
+            Err(Error::HoldNotFound { .. })
+        ));
+        assert_eq!(lending.renew(book, date(10)).unwrap().due, date(17));
+    }
+}
@@ -1,2 +1,11 @@
 pub mod book;
 pub mod bookshelf;
+pub mod error;
+pub mod export;
+mod import;
+mod index;
+pub mod isbn;
+pub mod lending;
+pub mod normalize;
+pub mod query;
+mod store;
@@ -0,0 +1,35 @@
+use unicode_normalization::UnicodeNormalization;
+
+// 検索のために文字列を揃える。本の項目にも検索クエリにも同じものを使う
+//   1. NFKC で全角英数字や半角カナを揃える (ＣｈａｔＧＰＴ → ChatGPT、ｶﾀｶﾅ → カタカナ)
+//   2. 大文字を小文字にする
+//   3. カタカナをひらがなにする
+pub fn normalize(s: &str) -> String {
+    s.nfkc()
+        .flat_map(char::to_lowercase)
+        .map(katakana_to_hiragana)
+        .collect()
+}
+
+fn katakana_to_hiragana(c: char) -> char {
+    match c {
+        // ァ〜ヶ と ヽヾ は、ひらがなの 0x60 後ろにある
+        'ァ'..='ヶ' | 'ヽ' | 'ヾ' => char::from_u32(c as u32 - 0x60).unwrap(),
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("ＣｈａｔＧＰＴ"), "chatgpt");
+        assert_eq!(normalize("ﾌﾟﾛｸﾞﾗﾐﾝｸﾞ"), "ぷろぐらみんぐ");
+        assert_eq!(normalize("プログラミング"), normalize("ぷろぐらみんぐ"));
+        assert_eq!(normalize("ＡＩを使って学ぼう！"), "aiを使って学ぼう!");
+        // 長音記号は変えない
+        assert_eq!(normalize("ユーザー"), "ゆーざー");
+    }
+}
@@ -0,0 +1,163 @@
+// 検索クエリ
+//
+// 空白で区切った語をすべて含む本を探す。語の前に項目名を付けると、その項目だけを探す
+//
+//   rust                    タイトル・著者・タグのどれかに rust を含む本
+//   author:山田 rust        著者が山田で、どこかに rust を含む本
+//   title:"Rust 入門"       空白を含む語は "" で囲む
+//
+// 知らない項目名 (例: foo:bar) は、項目名ではなく語の一部として扱う
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Title,
+    Author,
+    Tag,
+}
+
+impl Field {
+    pub const ALL: [Field; 3] = [Field::Title, Field::Author, Field::Tag];
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "title" => Some(Field::Title),
+            "author" => Some(Field::Author),
+            "tag" => Some(Field::Tag),
+            _ => None,
+        }
+    }
+}
+
+// 項目ごとの重み。一致したときのスコアに掛ける
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weights {
+    pub title: i64,
+    pub author: i64,
+    pub tag: i64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            title: 3,
+            author: 2,
+            tag: 1,
+        }
+    }
+}
+
+impl Weights {
+    pub fn of(&self, field: Field) -> i64 {
+        match field {
+            Field::Title => self.title,
+            Field::Author => self.author,
+            Field::Tag => self.tag,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    // None ならすべての項目を探す
+    pub field: Option<Field>,
+    pub text: String,
+}
+
+impl Term {
+    pub fn fields(&self) -> &[Field] {
+        match &self.field {
+            Some(field) => std::slice::from_ref(field),
+            None => &Field::ALL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Self {
+        let terms = split_words(query)
+            .into_iter()
+            .filter_map(|word| {
+                let field = word
+                    .split_once(':')
+                    .and_then(|(name, text)| Some((Field::from_name(name)?, text)));
+                let term = match field {
+                    Some((field, text)) => Term {
+                        field: Some(field),
+                        text: unquote(text),
+                    },
+                    None => Term {
+                        field: None,
+                        text: unquote(&word),
+                    },
+                };
+                (!term.text.is_empty()).then_some(term)
+            })
+            .collect();
+        Self { terms }
+    }
+}
+
+// "" の中の空白では区切らない
+fn split_words(query: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in query.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn unquote(text: &str) -> String {
+    text.replace('"', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let query = Query::parse(r#"author:山田  rust title:"Rust 入門" foo:bar tag:"#);
+        assert_eq!(
+            query.terms,
+            vec![
+                Term {
+                    field: Some(Field::Author),
+                    text: "山田".to_string()
+                },
+                Term {
+                    field: None,
+                    text: "rust".to_string()
+                },
+                Term {
+                    field: Some(Field::Title),
+                    text: "Rust 入門".to_string()
+                },
+                Term {
+                    field: None,
+                    text: "foo:bar".to_string()
+                },
+            ]
+        );
+    }
+}
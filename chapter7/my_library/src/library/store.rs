@@ -0,0 +1,262 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{book::Book, bookshelf::Bookshelf, error::Error};
+
+// 本棚をファイルに保存する。保存するのは本だけで、検索のインデックスは読み込むときに作り直す
+impl Bookshelf {
+    // 拡張子が .db か .sqlite なら SQLite、それ以外は JSON のファイルから読む
+    // ファイルがなければ空の本棚にする
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        if is_sqlite(path) {
+            Self::load_sqlite(path)
+        } else {
+            Self::load_json(path)
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if is_sqlite(path) {
+            self.save_sqlite(path)
+        } else {
+            self.save_json(path)
+        }
+    }
+
+    // 本の配列の JSON として保存する
+    pub fn save_json(&self, path: &Path) -> Result<(), Error> {
+        let books: Vec<&Book> = self.books().collect();
+        let json = serde_json::to_string_pretty(&books).map_err(|e| Error::Json {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        write_atomic(path, &(json + "\n"))
+    }
+
+    // id のない本 (手で書いたファイルや、ID を導入する前のファイル) には ID をつけて、すぐに保存し直す
+    // 保存しないと、読むたびに別の ID になり、list で見た ID や貸出の記録が使えなくなる
+    pub fn load_json(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path)?;
+        let stored: Vec<StoredBook> = serde_json::from_str(&text).map_err(|e| Error::Json {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        let missing_ids = stored.iter().any(|stored| stored.id.is_none());
+        let books = stored.into_iter().map(|stored| match stored.id {
+            Some(id) => Book { id, ..stored.book },
+            None => stored.book,
+        });
+        let shelf = Self::from_books(books)?;
+        if missing_ids {
+            shelf.save_json(path)?;
+        }
+        Ok(shelf)
+    }
+
+    // SQLite のデータベースに保存する。前に保存した本は、すべて置き換える
+    pub fn save_sqlite(&self, path: &Path) -> Result<(), Error> {
+        let mut connection = open(path)?;
+        let transaction = connection.transaction()?;
+        transaction.execute_batch("DELETE FROM authors; DELETE FROM tags; DELETE FROM books;")?;
+        for (position, book) in self.books().enumerate() {
+            transaction.execute(
+                "INSERT INTO books (id, position, title, reading, isbn, publisher, year)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    book.id.to_string(),
+                    position,
+                    book.title,
+                    book.reading,
+                    book.isbn.as_ref().map(|isbn| isbn.to_string()),
+                    book.publisher,
+                    book.year,
+                ],
+            )?;
+            for (position, author) in book.authors.iter().enumerate() {
+                transaction.execute(
+                    "INSERT INTO authors (book_id, position, name) VALUES (?1, ?2, ?3)",
+                    params![book.id.to_string(), position, author],
+                )?;
+            }
+            for (position, tag) in book.tags.iter().enumerate() {
+                transaction.execute(
+                    "INSERT INTO tags (book_id, position, name) VALUES (?1, ?2, ?3)",
+                    params![book.id.to_string(), position, tag],
+                )?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    pub fn load_sqlite(path: &Path) -> Result<Self, Error> {
+        let connection = open(path)?;
+        let mut statement = connection.prepare(
+            "SELECT id, title, reading, isbn, publisher, year FROM books ORDER BY position",
+        )?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<u16>>(5)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut books = vec![];
+        for (id, title, reading, isbn, publisher, year) in rows {
+            let invalid = |message: String| Error::InvalidBook(format!("{}: {}", id, message));
+            let book = Book {
+                id: Uuid::parse_str(&id).map_err(|e| invalid(e.to_string()))?,
+                title,
+                reading,
+                authors: names(&connection, "authors", &id)?,
+                isbn: isbn.map(|isbn| isbn.parse()).transpose()?,
+                publisher,
+                year,
+                tags: names(&connection, "tags", &id)?,
+            };
+            books.push(book);
+        }
+        Self::from_books(books)
+    }
+}
+
+// JSON に保存した本。Book の id は省略すると新しい ID になるので、省略されたかを別に読む
+#[derive(Deserialize)]
+struct StoredBook {
+    id: Option<Uuid>,
+    #[serde(flatten)]
+    book: Book,
+}
+
+fn is_sqlite(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("db" | "sqlite")
+    )
+}
+
+fn open(path: &Path) -> Result<Connection, Error> {
+    let connection = Connection::open(path)?;
+    connection.execute_batch(
+        "PRAGMA foreign_keys = ON;
+        CREATE TABLE IF NOT EXISTS books (
+            id TEXT PRIMARY KEY,
+            position INTEGER NOT NULL,
+            title TEXT NOT NULL,
+            reading TEXT,
+            isbn TEXT UNIQUE,
+            publisher TEXT,
+            year INTEGER
+        );
+        CREATE TABLE IF NOT EXISTS authors (
+            book_id TEXT NOT NULL REFERENCES books(id),
+            position INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            PRIMARY KEY (book_id, position)
+        );
+        CREATE TABLE IF NOT EXISTS tags (
+            book_id TEXT NOT NULL REFERENCES books(id),
+            position INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            PRIMARY KEY (book_id, position)
+        );",
+    )?;
+    Ok(connection)
+}
+
+// 著者やタグを、保存した順に読む
+fn names(connection: &Connection, table: &str, id: &str) -> Result<Vec<String>, Error> {
+    let mut statement = connection.prepare(&format!(
+        "SELECT name FROM {} WHERE book_id = ?1 ORDER BY position",
+        table
+    ))?;
+    let names = statement
+        .query_map([id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(names)
+}
+
+// 書き込み中に止まっても元のファイルが壊れないように、一時ファイルを置き換える
+// 置き換える前に、一時ファイルの中身をディスクに書き込んでおく
+pub(super) fn write_atomic(path: &Path, text: &str) -> Result<(), Error> {
+    let tmp = with_suffix(path, ".tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+pub(super) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookshelf() -> Bookshelf {
+        let rust = Book::new("プログラミングRust", "Jim Blandy")
+            .author("Jason Orendorff")
+            .isbn("9784873118550".parse().unwrap())
+            .publisher("オライリー・ジャパン")
+            .year(2018)
+            .tag("rust")
+            .tag("技術書");
+        let python = Book::new("Pythonプログラミング入門", "山田花子").reading("ぱいそん");
+        Bookshelf::from_books([rust, python]).unwrap()
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let shelf = bookshelf();
+        let expected: Vec<Book> = shelf.books().cloned().collect();
+
+        let json = dir.path().join("books.json");
+        assert!(Bookshelf::load(&json).unwrap().is_empty());
+        shelf.save(&json).unwrap();
+        let loaded = Bookshelf::load_json(&json).unwrap();
+        assert_eq!(loaded.books().cloned().collect::<Vec<_>>(), expected);
+        assert_eq!(loaded.search("ぱいそん").len(), 1);
+
+        // 2回保存しても、前の本が残らない
+        let db = dir.path().join("books.db");
+        shelf.save_sqlite(&db).unwrap();
+        shelf.save_sqlite(&db).unwrap();
+        let loaded = Bookshelf::load(&db).unwrap();
+        assert_eq!(loaded.books().cloned().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_load_json_without_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = dir.path().join("books.json");
+        std::fs::write(
+            &json,
+            r#"[{"title": "プログラミングRust", "authors": ["Jim Blandy"]}]"#,
+        )
+        .unwrap();
+        let first = Bookshelf::load(&json).unwrap();
+        let second = Bookshelf::load(&json).unwrap();
+        let id = first.books().next().unwrap().id;
+        assert_eq!(second.books().next().unwrap().id, id);
+    }
+}